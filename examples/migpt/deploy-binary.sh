@@ -1,12 +1,14 @@
 #!/bin/bash
 
 # HTTP Server Deployment Script
-# Usage: ./deploy-http-server.sh <remote-server-ip> [remote-user]
+# Usage: ./deploy-http-server.sh <remote-server-ip> [remote-user] [config-file]
 
 set -e
 
 REMOTE_SERVER="$1"
 REMOTE_USER="${2:-root}"
+CONFIG_FILE="${3:-./config.json}"
+REMOTE_CONFIG="/opt/xiaoai/$(basename "$CONFIG_FILE")"
 BINARY_PATH="./target/release/http_server"
 REMOTE_PATH="/opt/xiaoai"
 SERVICE_NAME="xiaoai-http-server"
@@ -39,6 +41,14 @@ scp "$BINARY_PATH" "$REMOTE_USER@$REMOTE_SERVER:$REMOTE_PATH/"
 # Make executable
 ssh "$REMOTE_USER@$REMOTE_SERVER" "chmod +x $REMOTE_PATH/http_server"
 
+# Copy config (the server refuses to start without a valid one)
+if [ -f "$CONFIG_FILE" ]; then
+    echo "📤 Copying config $CONFIG_FILE..."
+    scp "$CONFIG_FILE" "$REMOTE_USER@$REMOTE_SERVER:$REMOTE_CONFIG"
+else
+    echo "⚠️  $CONFIG_FILE not found, make sure $REMOTE_CONFIG exists on the server"
+fi
+
 # Create systemd service
 echo "⚙️  Creating systemd service..."
 ssh "$REMOTE_USER@$REMOTE_SERVER" "cat > /etc/systemd/system/$SERVICE_NAME.service << 'EOF'
@@ -50,7 +60,7 @@ After=network.target
 Type=simple
 User=root
 WorkingDirectory=$REMOTE_PATH
-ExecStart=$REMOTE_PATH/http_server
+ExecStart=$REMOTE_PATH/http_server --config $REMOTE_CONFIG
 Restart=always
 RestartSec=5
 Environment=RUST_LOG=info
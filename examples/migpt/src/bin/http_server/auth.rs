@@ -0,0 +1,286 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use warp::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use warp::http::{HeaderMap, Method, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::path::FullPath;
+use warp::{Filter, Rejection, Reply};
+
+use open_xiaoai::services::auth;
+use open_xiaoai::services::config::{Validate, Validator};
+
+use crate::store::Store;
+
+/// Largest request body accepted by authenticated endpoints
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Admin token with access to every endpoint. Without it the server accepts anyone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Token devices present at /register to get their own secret. Defaults to the admin token.
+    #[serde(rename = "registrationToken", default, skip_serializing_if = "Option::is_none")]
+    pub registration_token: Option<String>,
+    /// Origins allowed to call the API from a browser, "*" allows any
+    #[serde(rename = "corsOrigins", default)]
+    pub cors_origins: Vec<String>,
+}
+
+impl Validate for AuthConfig {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(token) = &self.token {
+            v.not_empty("auth.token", token);
+        }
+        if let Some(token) = &self.registration_token {
+            v.not_empty("auth.registrationToken", token);
+            if self.token.is_none() {
+                v.error("auth.registrationToken", "requires auth.token");
+            }
+        }
+        for (i, origin) in self.cors_origins.iter().enumerate() {
+            if origin != "*" {
+                v.url(&format!("auth.corsOrigins.{}", i), origin);
+            }
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn enabled(&self) -> bool {
+        self.token.is_some()
+    }
+}
+
+/// CORS for the given origins, "*" allows any
+pub fn cors(origins: &[String]) -> warp::cors::Builder {
+    let cors = warp::cors()
+        .allow_headers(vec![
+            "content-type",
+            "authorization",
+            auth::CLIENT_ID_HEADER,
+            auth::TIMESTAMP_HEADER,
+            auth::SIGNATURE_HEADER,
+            "cf-access-client-id",
+            "cf-access-client-secret",
+        ])
+        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
+    if origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(origins.iter().map(String::as_str))
+    }
+}
+
+/// Who made a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    /// Holder of the admin token, or anyone when auth is disabled
+    Admin,
+    /// Holder of the registration token, may only register devices
+    Registrar,
+    /// A registered device, authenticated with its own secret
+    Device(String),
+}
+
+impl Principal {
+    /// Whether this principal may read and acknowledge the given device's commands
+    pub fn can_act_as(&self, client_id: &str) -> bool {
+        match self {
+            Principal::Admin => true,
+            Principal::Device(id) => id == client_id,
+            Principal::Registrar => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+#[derive(Debug)]
+pub struct Forbidden;
+
+impl warp::reject::Reject for Forbidden {}
+
+#[derive(Debug)]
+pub struct BadRequest(pub String);
+
+impl warp::reject::Reject for BadRequest {}
+
+/// Checks request credentials against the config and the issued device secrets
+pub struct Authenticator {
+    config: AuthConfig,
+    store: Arc<dyn Store>,
+}
+
+impl Authenticator {
+    pub fn new(config: AuthConfig, store: Arc<dyn Store>) -> Self {
+        Self { config, store }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled()
+    }
+
+    /// A device authenticates with `X-Client-Id` plus either `Authorization: Bearer <secret>`
+    /// or an HMAC signature in `X-Timestamp` / `X-Signature`. Scripts use the admin token.
+    pub fn authenticate(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Option<Principal> {
+        let Some(admin_token) = &self.config.token else {
+            return Some(Principal::Admin);
+        };
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let bearer = header(AUTHORIZATION.as_str())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+
+        if let Some(client_id) = header(auth::CLIENT_ID_HEADER) {
+            let secret = match self.store.device_secret(client_id) {
+                Ok(secret) => secret?,
+                Err(e) => {
+                    eprintln!("❌ Failed to load credentials for client {}: {}", client_id, e);
+                    return None;
+                }
+            };
+            let valid = match bearer {
+                Some(token) => auth::constant_time_eq(token.as_bytes(), secret.as_bytes()),
+                None => {
+                    let timestamp = header(auth::TIMESTAMP_HEADER)?.parse().ok()?;
+                    let signature = header(auth::SIGNATURE_HEADER)?;
+                    let now = chrono::Utc::now().timestamp();
+                    auth::verify(&secret, method.as_str(), path, timestamp, body, signature, now)
+                }
+            };
+            return valid.then(|| Principal::Device(client_id.to_string()));
+        }
+
+        let token = bearer?.as_bytes();
+        if auth::constant_time_eq(token, admin_token.as_bytes()) {
+            return Some(Principal::Admin);
+        }
+        let registration_token = self.config.registration_token.as_ref()?;
+        auth::constant_time_eq(token, registration_token.as_bytes()).then_some(Principal::Registrar)
+    }
+
+    /// A new random device secret
+    pub fn issue_secret() -> String {
+        format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        )
+    }
+}
+
+/// Authenticates the request, passing on the raw body since signatures cover it
+pub fn authenticated(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (Principal, Bytes), Error = Rejection> + Clone {
+    // content_length_limit would also reject bodiless GETs, which carry no content-length
+    let size_limit = warp::header::optional::<u64>("content-length")
+        .and_then(|length: Option<u64>| async move {
+            match length {
+                Some(length) if length > MAX_BODY_SIZE => {
+                    Err(warp::reject::custom(BadRequest("request body too large".to_string())))
+                }
+                _ => Ok(()),
+            }
+        })
+        .untuple_one();
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(size_limit)
+        .and(warp::body::bytes())
+        .and_then(move |method: Method, path: FullPath, headers: HeaderMap, body: Bytes| {
+            let authenticator = authenticator.clone();
+            async move {
+                match authenticator.authenticate(&method, path.as_str(), &headers, &body) {
+                    Some(principal) => Ok((principal, body)),
+                    None => Err(warp::reject::custom(Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// The authenticated principal of a request without a body
+pub fn principal(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (Principal,), Error = Rejection> + Clone {
+    authenticated(authenticator).map(|principal: Principal, _body: Bytes| principal)
+}
+
+/// The authenticated principal and the JSON body of a request
+pub fn json_body<T: DeserializeOwned + Send>(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (Principal, T), Error = Rejection> + Clone {
+    authenticated(authenticator)
+        .and_then(|principal: Principal, body: Bytes| async move {
+            serde_json::from_slice::<T>(&body)
+                .map(|value| (principal, value))
+                .map_err(|e| warp::reject::custom(BadRequest(e.to_string())))
+        })
+        .untuple_one()
+}
+
+/// The JSON body of a request only the admin may make
+pub fn admin_json_body<T: DeserializeOwned + Send>(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    json_body(authenticator).and_then(|principal: Principal, value: T| async move {
+        match principal {
+            Principal::Admin => Ok(value),
+            _ => Err(warp::reject::custom(Forbidden)),
+        }
+    })
+}
+
+/// Only the admin may pass
+pub fn admin(
+    authenticator: Arc<Authenticator>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    principal(authenticator)
+        .and_then(|principal: Principal| async move {
+            match principal {
+                Principal::Admin => Ok(()),
+                _ => Err(warp::reject::custom(Forbidden)),
+            }
+        })
+        .untuple_one()
+}
+
+/// Turns auth rejections into JSON errors
+pub async fn handle_rejection(rejection: Rejection) -> Result<warp::reply::Response, Rejection> {
+    if rejection.find::<Unauthorized>().is_some() {
+        let reply = warp::reply::with_status(
+            warp::reply::json(&json!({"error": "unauthorized"})),
+            StatusCode::UNAUTHORIZED,
+        );
+        return Ok(warp::reply::with_header(reply, WWW_AUTHENTICATE, "Bearer").into_response());
+    }
+    if rejection.find::<Forbidden>().is_some() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({"error": "forbidden"})),
+            StatusCode::FORBIDDEN,
+        )
+        .into_response());
+    }
+    if let Some(BadRequest(message)) = rejection.find::<BadRequest>() {
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({"error": message})),
+            StatusCode::BAD_REQUEST,
+        )
+        .into_response());
+    }
+    Err(rejection)
+}
@@ -0,0 +1,1079 @@
+mod auth;
+mod registry;
+mod store;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+use warp::{http::StatusCode, Filter, Reply};
+
+use open_xiaoai::services::command::{CommandOutcome, DeviceCommand};
+use open_xiaoai::services::config::{self, OpenAIConfig, PromptConfig, Validate, Validator};
+use open_xiaoai::services::connect::data::{Event, Request, Response};
+
+use auth::{AuthConfig, Authenticator, Principal};
+use registry::{DeviceReport, DevicesConfig, Registry};
+use store::{EventQuery, StorageConfig, Store, StoredEvent};
+
+/// Server config, loaded through the same loader as the client:
+/// the keys and tokens may be `env:NAME` / `file:PATH` and every field is validated on startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub openai: OpenAIConfig,
+    #[serde(default)]
+    pub prompt: PromptConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub devices: DevicesConfig,
+}
+
+impl Validate for ServerConfig {
+    fn secret_fields() -> &'static [&'static str] {
+        &["openai.apiKey", "openai.fallbacks.*.apiKey", "auth.token", "auth.registrationToken"]
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        self.openai.validate(v);
+        v.not_empty("prompt.system", &self.prompt.system);
+        self.storage.validate(v);
+        self.auth.validate(v);
+        self.devices.validate(v);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmService {
+    config: ServerConfig,
+    client: reqwest::Client,
+}
+
+impl LlmService {
+    pub fn new(config: ServerConfig) -> Self {
+        let client = reqwest::Client::new();
+        Self { config, client }
+    }
+
+    pub async fn call_llm(&self, instruction: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!("{}/chat/completions", self.config.openai.base_url);
+        
+        let messages = vec![
+            json!({
+                "role": "system",
+                "content": self.config.prompt.system
+            }),
+            json!({
+                "role": "user", 
+                "content": instruction
+            })
+        ];
+
+        let body = json!({
+            "model": self.config.openai.model,
+            "messages": messages,
+            "temperature": self.config.openai.temperature.unwrap_or(0.7),
+            "max_tokens": self.config.openai.max_tokens.unwrap_or(1000)
+        });
+
+        println!("🤖 Sending LLM request: {}", instruction);
+        
+        let response = self.client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.config.openai.api_key))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("LLM API error {}: {}", status, text).into());
+        }
+
+        let json: Value = response.json().await?;
+        
+        if let Some(content) = json["choices"][0]["message"]["content"].as_str() {
+            println!("🎯 LLM response: {}", content);
+            Ok(content.to_string())
+        } else {
+            Err("No content in LLM response".into())
+        }
+    }
+}
+
+pub fn extract_instruction_text(text: &str) -> Option<String> {
+    // Try to extract instruction from various formats
+    if let Some(caps) = Regex::new(r#""instruction":\s*"([^"]+)""#).unwrap().captures(text) {
+        return Some(caps[1].to_string());
+    }
+    
+    if let Some(caps) = Regex::new(r#"instruction[："]\s*([^，。\n]+)"#).unwrap().captures(text) {
+        return Some(caps[1].to_string());
+    }
+    
+    // If it's already a plain instruction, return as-is
+    if !text.contains("{") && !text.contains(":") && text.len() > 2 {
+        return Some(text.to_string());
+    }
+    
+    None
+}
+
+/// Config path from `--config <path>`, then the CONFIG_PATH env var, then ./config.json.
+/// Files ending in .toml are read as TOML, anything else as JSON.
+fn config_path() -> String {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return path.to_string();
+        }
+        if arg == "--config" {
+            match args.next() {
+                Some(path) => return path,
+                None => {
+                    eprintln!("❌ --config requires a path, e.g. --config config.toml");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.json".to_string())
+}
+
+fn read_config() -> ServerConfig {
+    let config_path = config_path();
+    println!("📄 Reading config from: {}", config_path);
+
+    match config::load::<ServerConfig>(&config_path) {
+        Ok(config) => {
+            println!("⚙️  LLM Config - URL: {}, Model: {}", config.openai.base_url, config.openai.model);
+            config
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub type RpcWaiter = (String, oneshot::Sender<CommandOutcome>);
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub store: Arc<dyn Store>,
+    pub storage: StorageConfig,
+    pub llm_service: LlmService,
+    /// /rpc calls waiting for the device to report the result, keyed by command id,
+    /// with the client id of the device the call was sent to
+    pub rpc_waiters: Arc<Mutex<HashMap<String, RpcWaiter>>>,
+    pub authenticator: Arc<Authenticator>,
+    pub cors_origins: Vec<String>,
+    pub registry: Arc<Registry>,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl ServerState {
+    pub fn new() -> Self {
+        let config = read_config();
+        match &config.storage.path {
+            Some(path) => println!("💾 Storing commands and events in {}", path),
+            None => println!("⚠️  No storage.path configured, commands and events are kept in memory only"),
+        }
+        if !config.auth.enabled() {
+            println!("⚠️  No auth.token configured, anyone who can reach the server may use it");
+        }
+        match Self::from_config(config) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("❌ Failed to open storage: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    pub fn from_config(config: ServerConfig) -> store::StoreResult<Self> {
+        let store = store::open(&config.storage)?;
+        let storage = config.storage.clone();
+        let cors_origins = config.auth.cors_origins.clone();
+        let authenticator = Arc::new(Authenticator::new(config.auth.clone(), store.clone()));
+        let registry = Arc::new(Registry::new(&config.devices, store.clone()));
+        let trusted_proxies = config.devices.trusted_proxies.clone();
+        let llm_service = LlmService::new(config);
+        
+        Ok(Self {
+            store,
+            storage,
+            llm_service,
+            rpc_waiters: Arc::new(Mutex::new(HashMap::new())),
+            authenticator,
+            cors_origins,
+            registry,
+            trusted_proxies,
+        })
+    }
+}
+
+fn forbidden() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&json!({"error": "forbidden"})),
+        StatusCode::FORBIDDEN,
+    )
+}
+
+async fn handle_register(
+    principal: Principal,
+    body: Value,
+    ip: Option<String>,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    if let Some(client_id) = body.get("clientId").and_then(|v| v.as_str()) {
+        // Registration tokens may register any device, a device may only re-register itself
+        if !(principal == Principal::Registrar || principal.can_act_as(client_id)) {
+            return Ok(forbidden());
+        }
+        let mut reply = json!({"status": "registered", "clientId": client_id});
+        if state.authenticator.enabled() {
+            // A returning device proved itself with its secret and keeps it.
+            // Registering an existing id with a token replaces the secret.
+            let known = state.store.device_secret(client_id).unwrap_or_default();
+            let secret = match (&principal, known) {
+                (Principal::Device(_), Some(secret)) => {
+                    println!("♻️  Returning client {} recognised by its secret", client_id);
+                    secret
+                }
+                (_, Some(_)) => {
+                    println!("🔑 Client {} registered again without its secret, issuing a new one", client_id);
+                    Authenticator::issue_secret()
+                }
+                (_, None) => Authenticator::issue_secret(),
+            };
+            if let Err(e) = state.store.set_device_secret(client_id, &secret) {
+                eprintln!("❌ Failed to store credentials for client {}: {}", client_id, e);
+                return Ok(warp::reply::with_status(
+                    warp::reply::json(&json!({"error": "storage error"})),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                ));
+            }
+            reply["secret"] = json!(secret);
+        }
+        if let Err(e) = state.registry.seen(client_id, DeviceReport::from_value(&body), ip) {
+            eprintln!("❌ Failed to update device record for client {}: {}", client_id, e);
+        }
+        // Commands queued while the client was away are kept for it
+        let pending = state.store.pending_commands(client_id).map(|c| c.len()).unwrap_or_default();
+        println!("📝 Client registered: {} ({} pending commands)", client_id, pending);
+        Ok(warp::reply::with_status(
+            warp::reply::json(&reply),
+            StatusCode::OK,
+        ))
+    } else {
+        Ok(warp::reply::with_status(
+            warp::reply::json(&json!({"error": "Missing clientId"})),
+            StatusCode::BAD_REQUEST,
+        ))
+    }
+}
+
+async fn handle_events(
+    principal: Principal,
+    mut event: Event,
+    ip: Option<String>,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    match &principal {
+        Principal::Admin => {}
+        // A device can only speak for itself
+        Principal::Device(client_id) => {
+            if let Some(data) = event.data.as_object_mut() {
+                data.insert("clientId".to_string(), json!(client_id));
+            }
+        }
+        Principal::Registrar => return Ok(forbidden()),
+    }
+    println!("📨 Event received: {} - {}", event.name, event.id);
+    
+    // Store the event
+    if let Err(e) = state.store.append_event(&StoredEvent::received(event.clone())) {
+        eprintln!("❌ Failed to store event {}: {}", event.id, e);
+    }
+    
+    // Any event proves the device is alive, heartbeats also carry its details
+    if let Some(client_id) = event.data.get("clientId").and_then(|v| v.as_str()) {
+        let report = match event.name.as_str() {
+            "heartbeat" => DeviceReport::from_value(&event.data),
+            _ => DeviceReport::default(),
+        };
+        if let Err(e) = state.registry.seen(client_id, report, ip) {
+            eprintln!("❌ Failed to update device record for client {}: {}", client_id, e);
+        }
+    }
+    
+    // Process text instruction events
+    if event.name == "instruction" {
+        if let Some(text) = event.data.get("text").and_then(|v| v.as_str()) {
+            if let Some(instruction) = extract_instruction_text(text) {
+                println!("🎯 Processing instruction: {}", instruction);
+                
+                // Call LLM service
+                match state.llm_service.call_llm(&instruction).await {
+                    Ok(response_text) => {
+                        println!("✅ LLM response: {}", response_text);
+                        
+                        // Create TTS command
+                        let tts_command = Response {
+                            id: Uuid::new_v4().to_string(),
+                            data: json!({
+                                "action": "tts",
+                                "text": response_text,
+                                "instruction": instruction
+                            }),
+                        };
+                        
+                        // Store command for client polling
+                        if let Some(client_id) = event.data.get("clientId").and_then(|v| v.as_str()) {
+                            match state.store.push_command(client_id, &tts_command, state.storage.command_expiry()) {
+                                Ok(()) => println!("🔊 TTS Command stored for client {}: {}", client_id, tts_command.id),
+                                Err(e) => eprintln!("❌ Failed to queue command for client {}: {}", client_id, e),
+                            }
+                        } else {
+                            println!("🔊 TTS Command created (no client_id): {}", tts_command.id);
+                        }
+                    }
+                    Err(e) => {
+                        println!("❌ LLM call failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+    
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({"status": "received", "eventId": event.id})),
+        StatusCode::OK,
+    ))
+}
+
+async fn handle_get_commands(
+    client_id: String,
+    principal: Principal,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    if !principal.can_act_as(&client_id) {
+        return Ok(forbidden());
+    }
+    let commands = match state.store.pending_commands(&client_id) {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!("❌ Failed to load commands for client {}: {}", client_id, e);
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"error": "storage error"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+    
+    // Commands stay queued until the client acknowledges them, so a lost response is simply redelivered
+    if !commands.is_empty() {
+        println!("📋 Sending {} commands to client: {}", commands.len(), client_id);
+    }
+    
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({"commands": commands})),
+        StatusCode::OK,
+    ))
+}
+
+async fn handle_push_command(
+    client_id: String,
+    command: DeviceCommand,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    let command = Response {
+        id: Uuid::new_v4().to_string(),
+        data: json!(command),
+    };
+    match state.store.push_command(&client_id, &command, state.storage.command_expiry()) {
+        Ok(()) => {
+            println!("📤 Command queued for client {}: {} {}", client_id, command.id, command.data["action"]);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"status": "queued", "commandId": command.id})),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to queue command for client {}: {}", client_id, e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"error": "storage error"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandAck {
+    #[serde(default)]
+    ids: Vec<String>,
+    /// Outcome of each executed command
+    #[serde(default)]
+    results: Vec<CommandOutcome>,
+}
+
+async fn handle_ack_commands(
+    client_id: String,
+    principal: Principal,
+    ack: CommandAck,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    if !principal.can_act_as(&client_id) {
+        return Ok(forbidden());
+    }
+    // Results are kept in the event history as "command.result"
+    for result in &ack.results {
+        if result.ok {
+            println!("✅ Command {} succeeded on client {}", result.id, client_id);
+        } else {
+            println!("⚠️  Command {} failed on client {}: {}", result.id, client_id, result.error.as_deref().unwrap_or_default());
+        }
+        let mut data = json!(result);
+        data["clientId"] = json!(client_id);
+        let event = Event::new("command.result", data);
+        if let Err(e) = state.store.append_event(&StoredEvent::received(event)) {
+            eprintln!("❌ Failed to store result of command {}: {}", result.id, e);
+        }
+        // Only the device the call was sent to can answer it
+        let waiter = match state.rpc_waiters.lock().unwrap().entry(result.id.clone()) {
+            Entry::Occupied(entry) if entry.get().0 == client_id => Some(entry.remove().1),
+            _ => None,
+        };
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(result.clone());
+        }
+    }
+
+    let mut ids = ack.ids;
+    for result in &ack.results {
+        if !ids.contains(&result.id) {
+            ids.push(result.id.clone());
+        }
+    }
+    match state.store.ack_commands(&client_id, &ids) {
+        Ok(acked) => {
+            if acked > 0 {
+                println!("✅ Client {} acknowledged {} commands", client_id, acked);
+            }
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"acked": acked})),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to acknowledge commands for client {}: {}", client_id, e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"error": "storage error"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+async fn handle_list_events(
+    query: EventQuery,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    match state.store.events(&query) {
+        Ok(events) => Ok(warp::reply::with_status(
+            warp::reply::json(&json!({"events": events})),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("❌ Failed to query events: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"error": "storage error"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+async fn handle_list_devices(state: Arc<ServerState>) -> Result<impl Reply, Infallible> {
+    match state.registry.list() {
+        Ok(devices) => Ok(warp::reply::with_status(
+            warp::reply::json(&json!({"devices": devices})),
+            StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("❌ Failed to list devices: {}", e);
+            Ok(warp::reply::with_status(
+                warp::reply::json(&json!({"error": "storage error"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Body of POST /rpc
+#[derive(Debug, Deserialize)]
+struct RpcCall {
+    #[serde(rename = "clientId")]
+    client_id: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Seconds to wait for the device, defaults to 30
+    timeout: Option<u64>,
+}
+
+/// Upper bound for RpcCall::timeout, the client may take a while to poll
+const MAX_RPC_TIMEOUT: u64 = 300;
+
+/// Forward a Request to the device over its command queue and wait for the Response
+async fn handle_rpc(
+    call: RpcCall,
+    state: Arc<ServerState>,
+) -> Result<impl Reply, Infallible> {
+    let request = Request::new(&call.method, call.params);
+    let timeout = call.timeout.unwrap_or(30).clamp(1, MAX_RPC_TIMEOUT);
+    println!("🔧 RPC request for client {}: {} - {}", call.client_id, request.method, request.id);
+
+    // The command expires with the call: the store stops delivering it and a
+    // device that fetched it late refuses to run it
+    let expires_at = chrono::Utc::now().timestamp_millis() + timeout as i64 * 1000;
+    let command = Response {
+        id: request.id.clone(),
+        data: json!(DeviceCommand::Rpc {
+            request: request.clone(),
+            expires_at: Some(expires_at),
+        }),
+    };
+    let (tx, rx) = oneshot::channel();
+    state.rpc_waiters.lock().unwrap().insert(request.id.clone(), (call.client_id.clone(), tx));
+    if let Err(e) = state.store.push_command(&call.client_id, &command, Some(expires_at)) {
+        state.rpc_waiters.lock().unwrap().remove(&request.id);
+        eprintln!("❌ Failed to queue RPC for client {}: {}", call.client_id, e);
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({"error": "storage error"})),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+
+    let outcome = tokio::time::timeout(Duration::from_secs(timeout), rx).await;
+    state.rpc_waiters.lock().unwrap().remove(&request.id);
+    match outcome {
+        Ok(Ok(outcome)) if outcome.ok => Ok(warp::reply::with_status(
+            warp::reply::json(&Response::new(&request.id, outcome.data.unwrap_or(Value::Null))),
+            StatusCode::OK,
+        )),
+        Ok(Ok(outcome)) => Ok(warp::reply::with_status(
+            warp::reply::json(&Response::from_error(
+                &request.id,
+                outcome.error.unwrap_or_else(|| "unknown error".to_string()),
+            )),
+            StatusCode::BAD_GATEWAY,
+        )),
+        _ => {
+            println!("⏰ RPC {} to client {} timed out after {}s", request.id, call.client_id, timeout);
+            if let Err(e) = state.store.ack_commands(&call.client_id, std::slice::from_ref(&request.id)) {
+                eprintln!("❌ Failed to drop RPC {}: {}", request.id, e);
+            }
+            Ok(warp::reply::with_status(
+                warp::reply::json(&Response::from_error(&request.id, "device did not respond in time")),
+                StatusCode::GATEWAY_TIMEOUT,
+            ))
+        }
+    }
+}
+
+async fn handle_test() -> Result<impl Reply, Infallible> {
+    Ok(warp::reply::json(&json!({
+        "status": "ok",
+        "message": "HTTP Server is running",
+        "timestamp": chrono::Utc::now().timestamp()
+    })))
+}
+
+/// Address of the caller. The first X-Forwarded-For hop is only used when the request comes
+/// from a trusted proxy, anyone else could claim any address with the header.
+pub fn client_ip(
+    trusted_proxies: Vec<IpAddr>,
+) -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("x-forwarded-for")
+        .and(warp::addr::remote())
+        .map(move |forwarded: Option<String>, remote: Option<SocketAddr>| {
+            let proxied = remote.is_some_and(|addr| trusted_proxies.contains(&addr.ip()));
+            forwarded
+                .filter(|_| proxied)
+                .and_then(|hops| hops.split(',').next().map(|hop| hop.trim().to_string()))
+                .filter(|hop| !hop.is_empty())
+                .or_else(|| remote.map(|addr| addr.ip().to_string()))
+        })
+}
+
+pub fn with_state(
+    state: Arc<ServerState>,
+) -> impl Filter<Extract = (Arc<ServerState>,), Error = Infallible> + Clone {
+    warp::any().map(move || state.clone())
+}
+
+/// All endpoints. /test and /update are public, everything else is authenticated
+pub fn routes(
+    state: Arc<ServerState>,
+    update_dir: &str,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    let authenticator = state.authenticator.clone();
+    
+    // Test endpoint
+    let test = warp::path("test")
+        .and(warp::get())
+        .and_then(handle_test);
+    
+    // Register endpoint, returns the device secret when auth is enabled
+    let register = warp::path("register")
+        .and(warp::post())
+        .and(auth::json_body(authenticator.clone()))
+        .and(client_ip(state.trusted_proxies.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_register);
+    
+    // Events endpoint
+    let events = warp::path("events")
+        .and(warp::post())
+        .and(auth::json_body(authenticator.clone()))
+        .and(client_ip(state.trusted_proxies.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_events);
+    
+    // Event history (GET /events?clientId=&name=&since=&limit=)
+    let list_events = warp::path("events")
+        .and(warp::get())
+        .and(auth::admin(authenticator.clone()))
+        .and(warp::query::<EventQuery>())
+        .and(with_state(state.clone()))
+        .and_then(handle_list_events);
+    
+    // Device registry (GET /devices)
+    let devices = warp::path("devices")
+        .and(warp::get())
+        .and(auth::admin(authenticator.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_list_devices);
+    
+    // Commands endpoint (GET /commands/{client_id})
+    let commands = warp::path!("commands" / String)
+        .and(warp::get())
+        .and(auth::principal(authenticator.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_get_commands);
+    
+    // Queue a device command (POST /commands/{client_id} {"action": "play_url", ...})
+    let push_command = warp::path!("commands" / String)
+        .and(warp::post())
+        .and(auth::admin_json_body(authenticator.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_push_command);
+    
+    // Acknowledge delivered commands (POST /commands/{client_id}/ack {"ids": [...], "results": [...]})
+    let ack_commands = warp::path!("commands" / String / "ack")
+        .and(warp::post())
+        .and(auth::json_body(authenticator.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_ack_commands);
+    
+    // Device RPC (POST /rpc {"clientId": "...", "method": "get_device_info", "params": {}, "timeout": 30})
+    let rpc = warp::path("rpc")
+        .and(warp::post())
+        .and(auth::admin_json_body(authenticator.clone()))
+        .and(with_state(state.clone()))
+        .and_then(handle_rpc);
+    
+    // Release files for client self-update (GET /update/manifest.json, GET /update/{binary})
+    let update = warp::path("update")
+        .and(warp::get())
+        .and(warp::fs::dir(update_dir.to_string()));
+    
+    let cors = auth::cors(&state.cors_origins);
+    
+    test
+        .or(register)
+        .or(events)
+        .or(list_events)
+        .or(devices)
+        .or(commands)
+        .or(push_command)
+        .or(ack_commands)
+        .or(rpc)
+        .or(update)
+        .recover(auth::handle_rejection)
+        .with(cors)
+}
+
+#[tokio::main]
+async fn main() {
+    println!("🚀 HTTP Server starting...");
+    
+    let state = Arc::new(ServerState::new());
+    
+    // Drop expired commands and events past their retention period
+    {
+        let store = state.store.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                match store.prune_commands() {
+                    Ok(0) => {}
+                    Ok(pruned) => println!("🧹 Dropped {} expired commands", pruned),
+                    Err(e) => eprintln!("❌ Failed to prune commands: {}", e),
+                }
+                match store.prune_events() {
+                    Ok(0) => {}
+                    Ok(pruned) => println!("🧹 Pruned {} expired events", pruned),
+                    Err(e) => eprintln!("❌ Failed to prune events: {}", e),
+                }
+            }
+        });
+    }
+    
+    // Mark devices that stopped sending heartbeats as offline
+    {
+        let registry = state.registry.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(registry.sweep_interval());
+            loop {
+                interval.tick().await;
+                if let Err(e) = registry.sweep() {
+                    eprintln!("❌ Failed to update device presence: {}", e);
+                }
+            }
+        });
+    }
+    
+    let update_dir = std::env::var("UPDATE_DIR").unwrap_or_else(|_| "./updates".to_string());
+    let routes = routes(state, &update_dir);
+    
+    println!("🌐 Server listening on http://0.0.0.0:4399");
+    println!("🔗 Test endpoint: http://localhost:4399/test");
+    println!("📦 Serving client updates from {}", update_dir);
+    
+    warp::serve(routes)
+        .run(([0, 0, 0, 0], 4399))
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use open_xiaoai::services::auth as signing;
+
+    const ADMIN_TOKEN: &str = "admin-token";
+    const REGISTRATION_TOKEN: &str = "registration-token";
+
+    fn state(auth: Value) -> Arc<ServerState> {
+        let config: ServerConfig = serde_json::from_value(json!({
+            "openai": {"baseURL": "http://127.0.0.1:9", "apiKey": "test", "model": "test"},
+            "auth": auth,
+        }))
+        .unwrap();
+        Arc::new(ServerState::from_config(config).unwrap())
+    }
+
+    fn secured() -> Arc<ServerState> {
+        state(json!({"token": ADMIN_TOKEN, "registrationToken": REGISTRATION_TOKEN}))
+    }
+
+    async fn register(state: &Arc<ServerState>, client_id: &str) -> String {
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .header("authorization", format!("Bearer {}", REGISTRATION_TOKEN))
+            .json(&json!({"clientId": client_id}))
+            .reply(&routes(state.clone(), "updates"))
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        body["secret"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn unauthenticated_calls_get_401() {
+        let routes = routes(secured(), "updates");
+        let calls = [
+            ("POST", "/register", json!({"clientId": "speaker"})),
+            ("POST", "/events", json!(Event::new("instruction", json!({"clientId": "speaker"})))),
+            ("GET", "/events", Value::Null),
+            ("GET", "/devices", Value::Null),
+            ("GET", "/commands/speaker", Value::Null),
+            ("POST", "/commands/speaker", json!({"action": "pause"})),
+            ("POST", "/commands/speaker/ack", json!({"ids": []})),
+            ("POST", "/rpc", json!({"clientId": "speaker", "method": "get_device_info"})),
+        ];
+        for (method, path, body) in calls {
+            let mut request = warp::test::request().method(method).path(path);
+            if !body.is_null() {
+                request = request.json(&body);
+            }
+            let res = request.reply(&routes).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{} {}", method, path);
+
+            // A wrong token is no better than none
+            let mut request = warp::test::request()
+                .method(method)
+                .path(path)
+                .header("authorization", "Bearer wrong");
+            if !body.is_null() {
+                request = request.json(&body);
+            }
+            let res = request.reply(&routes).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{} {}", method, path);
+        }
+
+        let res = warp::test::request().path("/test").reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn registered_device_authenticates_with_bearer_or_hmac() {
+        let state = secured();
+        let secret = register(&state, "speaker").await;
+        let routes = routes(state.clone(), "updates");
+
+        let res = warp::test::request()
+            .path("/commands/speaker")
+            .header(signing::CLIENT_ID_HEADER, "speaker")
+            .header("authorization", format!("Bearer {}", secret))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = serde_json::to_vec(&json!({"ids": ["1"]})).unwrap();
+        let timestamp = chrono::Utc::now().timestamp();
+        let signature = signing::sign(&secret, "POST", "/commands/speaker/ack", timestamp, &body);
+        let signed = |signature: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/commands/speaker/ack")
+                .header(signing::CLIENT_ID_HEADER, "speaker")
+                .header(signing::TIMESTAMP_HEADER, timestamp.to_string())
+                .header(signing::SIGNATURE_HEADER, signature)
+                .body(body.clone())
+        };
+        assert_eq!(signed(&signature).reply(&routes).await.status(), StatusCode::OK);
+        let tampered = signing::sign("wrong", "POST", "/commands/speaker/ack", timestamp, &body);
+        assert_eq!(signed(&tampered).reply(&routes).await.status(), StatusCode::UNAUTHORIZED);
+
+        // Re-registering rotates the secret
+        register(&state, "speaker").await;
+        let res = warp::test::request()
+            .path("/commands/speaker")
+            .header(signing::CLIENT_ID_HEADER, "speaker")
+            .header("authorization", format!("Bearer {}", secret))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn returning_device_keeps_its_secret() {
+        let state = secured();
+        let secret = register(&state, "speaker").await;
+        let routes = routes(state.clone(), "updates");
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .header(signing::CLIENT_ID_HEADER, "speaker")
+            .header("authorization", format!("Bearer {}", secret))
+            .json(&json!({"clientId": "speaker"}))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["secret"], secret.as_str());
+
+        // Its secret does not let it register as someone else
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .header(signing::CLIENT_ID_HEADER, "speaker")
+            .header("authorization", format!("Bearer {}", secret))
+            .json(&json!({"clientId": "other"}))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn devices_only_reach_their_own_commands() {
+        let state = secured();
+        let secret = register(&state, "speaker").await;
+        let routes = routes(state.clone(), "updates");
+        let as_device = |method: &str, path: &str| {
+            warp::test::request()
+                .method(method)
+                .path(path)
+                .header(signing::CLIENT_ID_HEADER, "speaker")
+                .header("authorization", format!("Bearer {}", secret))
+        };
+
+        let res = as_device("GET", "/commands/other").reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = as_device("GET", "/events").reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = as_device("POST", "/commands/speaker")
+            .json(&json!({"action": "pause"}))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // The registration token only registers
+        let res = warp::test::request()
+            .path("/commands/speaker")
+            .header("authorization", format!("Bearer {}", REGISTRATION_TOKEN))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/commands/speaker")
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .json(&json!({"action": "pause"}))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = as_device("GET", "/commands/speaker").reply(&routes).await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["commands"][0]["data"]["action"], "pause");
+    }
+
+    #[tokio::test]
+    async fn registered_devices_are_listed_with_presence() {
+        let state = secured();
+        let routes = routes(state.clone(), "updates");
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .header("authorization", format!("Bearer {}", REGISTRATION_TOKEN))
+            .remote_addr("10.0.0.2:5000".parse().unwrap())
+            .json(&json!({"clientId": "speaker", "version": "1.0.0", "device": {"model": "LX06"}}))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = warp::test::request()
+            .path("/devices")
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        let device = &body["devices"][0];
+        assert_eq!(device["clientId"], "speaker");
+        assert_eq!(device["model"], "LX06");
+        assert_eq!(device["version"], "1.0.0");
+        assert_eq!(device["ip"], "10.0.0.2");
+        assert_eq!(device["online"], true);
+    }
+
+    #[tokio::test]
+    async fn forwarded_address_is_only_trusted_from_proxies() {
+        let config: ServerConfig = serde_json::from_value(json!({
+            "openai": {"baseURL": "http://127.0.0.1:9", "apiKey": "test", "model": "test"},
+            "devices": {"trustedProxies": ["172.16.0.1"]},
+        }))
+        .unwrap();
+        let state = Arc::new(ServerState::from_config(config).unwrap());
+        let routes = routes(state.clone(), "updates");
+        let register = |client_id: &str, remote: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/register")
+                .remote_addr(remote.parse().unwrap())
+                .header("x-forwarded-for", "10.0.0.2, 172.16.0.1")
+                .json(&json!({"clientId": client_id}))
+        };
+        assert_eq!(register("proxied", "172.16.0.1:5000").reply(&routes).await.status(), StatusCode::OK);
+        assert_eq!(register("direct", "192.168.1.7:5000").reply(&routes).await.status(), StatusCode::OK);
+
+        let devices = state.registry.list().unwrap();
+        let ip = |client_id: &str| {
+            devices.iter().find(|device| device.client_id == client_id).unwrap().ip.clone()
+        };
+        assert_eq!(ip("proxied").as_deref(), Some("10.0.0.2"));
+        assert_eq!(ip("direct").as_deref(), Some("192.168.1.7"));
+    }
+
+    #[tokio::test]
+    async fn rpc_is_only_answered_by_its_device() {
+        let state = secured();
+        let speaker = register(&state, "speaker").await;
+        let other = register(&state, "other").await;
+        let routes = routes(state.clone(), "updates");
+
+        let call = tokio::spawn({
+            let routes = routes.clone();
+            async move {
+                warp::test::request()
+                    .method("POST")
+                    .path("/rpc")
+                    .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+                    .json(&json!({"clientId": "speaker", "method": "get_volume", "timeout": 5}))
+                    .reply(&routes)
+                    .await
+            }
+        });
+        let id = loop {
+            if let Some(id) = state.rpc_waiters.lock().unwrap().keys().next().cloned() {
+                break id;
+            }
+            tokio::task::yield_now().await;
+        };
+        let ack = |client_id: &str, secret: &str, volume: u8| {
+            warp::test::request()
+                .method("POST")
+                .path(&format!("/commands/{}/ack", client_id))
+                .header(signing::CLIENT_ID_HEADER, client_id)
+                .header("authorization", format!("Bearer {}", secret))
+                .json(&json!({"results": [{"id": id, "ok": true, "data": {"volume": volume}}]}))
+        };
+
+        // Another device acking the same id does not answer the call
+        assert_eq!(ack("other", &other, 0).reply(&routes).await.status(), StatusCode::OK);
+        assert!(state.rpc_waiters.lock().unwrap().contains_key(&id));
+
+        assert_eq!(ack("speaker", &speaker, 40).reply(&routes).await.status(), StatusCode::OK);
+        let res = call.await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body["data"]["volume"], 40);
+    }
+
+    #[tokio::test]
+    async fn server_without_token_stays_open() {
+        let routes = routes(state(json!({})), "updates");
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .json(&json!({"clientId": "speaker"}))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(body.get("secret").is_none());
+
+        let res = warp::test::request().path("/commands/speaker").reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
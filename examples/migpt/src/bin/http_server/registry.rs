@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use open_xiaoai::services::config::{Validate, Validator};
+use open_xiaoai::services::connect::data::Event;
+
+use crate::store::{Store, StoreResult, StoredEvent};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevicesConfig {
+    /// Seconds without a heartbeat or poll before a device counts as offline
+    #[serde(rename = "offlineAfter", default = "DevicesConfig::default_offline_after")]
+    pub offline_after: u64,
+    /// Reverse proxies whose X-Forwarded-For header is trusted for the device address.
+    /// Requests from anywhere else are recorded with their own address.
+    #[serde(rename = "trustedProxies", default)]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl DevicesConfig {
+    fn default_offline_after() -> u64 {
+        30
+    }
+}
+
+impl Default for DevicesConfig {
+    fn default() -> Self {
+        Self {
+            offline_after: DevicesConfig::default_offline_after(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+impl Validate for DevicesConfig {
+    fn validate(&self, v: &mut Validator) {
+        if self.offline_after == 0 {
+            v.error("devices.offlineAfter", "must be greater than 0");
+        }
+    }
+}
+
+/// What the server knows about one device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceRecord {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    /// Client version
+    pub version: Option<String>,
+    pub ip: Option<String>,
+    /// Unix milliseconds of the first registration
+    #[serde(rename = "registeredAt")]
+    pub registered_at: i64,
+    /// Unix milliseconds of the last request from the device
+    #[serde(rename = "lastSeen")]
+    pub last_seen: i64,
+    pub online: bool,
+}
+
+/// Device details reported at /register and in heartbeats:
+/// `{"version": "...", "device": {"model": "...", "serial": "..."}}`
+#[derive(Debug, Clone, Default)]
+pub struct DeviceReport {
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub version: Option<String>,
+}
+
+impl DeviceReport {
+    pub fn from_value(data: &Value) -> Self {
+        let text = |value: &Value| value.as_str().map(str::to_string);
+        Self {
+            model: data.pointer("/device/model").and_then(text),
+            serial: data.pointer("/device/serial").and_then(text),
+            version: data.get("version").and_then(text),
+        }
+    }
+}
+
+/// Device records with presence derived from heartbeats.
+/// Every online/offline transition is recorded as a `device.online` / `device.offline` event.
+pub struct Registry {
+    store: Arc<dyn Store>,
+    offline_after: Duration,
+    /// Serializes read-modify-write of records
+    lock: Mutex<()>,
+}
+
+impl Registry {
+    pub fn new(config: &DevicesConfig, store: Arc<dyn Store>) -> Self {
+        Self {
+            store,
+            offline_after: Duration::from_secs(config.offline_after),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// How often to look for devices that went silent
+    pub fn sweep_interval(&self) -> Duration {
+        (self.offline_after / 2).max(Duration::from_secs(1))
+    }
+
+    /// Record that a device made a request, creating its record on first contact
+    pub fn seen(&self, client_id: &str, report: DeviceReport, ip: Option<String>) -> StoreResult<DeviceRecord> {
+        let _guard = self.lock.lock().unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        let previous = self.store.device(client_id)?;
+        let was_online = previous.as_ref().is_some_and(|record| record.online);
+        let mut record = previous.unwrap_or_else(|| DeviceRecord {
+            client_id: client_id.to_string(),
+            model: None,
+            serial: None,
+            version: None,
+            ip: None,
+            registered_at: now,
+            last_seen: now,
+            online: false,
+        });
+        record.model = report.model.or(record.model);
+        record.serial = report.serial.or(record.serial);
+        record.version = report.version.or(record.version);
+        record.ip = ip.or(record.ip);
+        record.last_seen = now;
+        record.online = true;
+        self.store.upsert_device(&record)?;
+        if !was_online {
+            self.transition(&record)?;
+        }
+        Ok(record)
+    }
+
+    /// Mark devices that stopped reporting as offline, returns the ones that just went offline
+    pub fn sweep(&self) -> StoreResult<Vec<DeviceRecord>> {
+        let _guard = self.lock.lock().unwrap();
+        let cutoff = chrono::Utc::now().timestamp_millis() - self.offline_after.as_millis() as i64;
+        let mut offline = Vec::new();
+        for mut record in self.store.devices()? {
+            if record.online && record.last_seen < cutoff {
+                record.online = false;
+                self.store.upsert_device(&record)?;
+                self.transition(&record)?;
+                offline.push(record);
+            }
+        }
+        Ok(offline)
+    }
+
+    /// All known devices, most recently seen first
+    pub fn list(&self) -> StoreResult<Vec<DeviceRecord>> {
+        self.sweep()?;
+        let mut devices = self.store.devices()?;
+        devices.sort_by_key(|record| std::cmp::Reverse(record.last_seen));
+        Ok(devices)
+    }
+
+    fn transition(&self, record: &DeviceRecord) -> StoreResult<()> {
+        let name = if record.online { "device.online" } else { "device.offline" };
+        println!(
+            "{} Client {} is {}",
+            if record.online { "🟢" } else { "🔴" },
+            record.client_id,
+            if record.online { "online" } else { "offline" }
+        );
+        let event = Event::new(name, json!(record));
+        self.store.append_event(&StoredEvent::received(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{EventQuery, MemoryStore, Retention};
+
+    fn registry(store: Arc<dyn Store>) -> Registry {
+        Registry::new(&DevicesConfig { offline_after: 30, ..DevicesConfig::default() }, store)
+    }
+
+    fn report(version: &str) -> DeviceReport {
+        DeviceReport::from_value(&json!({
+            "version": version,
+            "device": {"model": "LX06", "serial": "12345/678"},
+        }))
+    }
+
+    fn transitions(store: &Arc<dyn Store>) -> Vec<String> {
+        let mut events = store.events(&EventQuery::default()).unwrap();
+        events.reverse();
+        events.into_iter().map(|event| event.event.name).collect()
+    }
+
+    #[test]
+    fn heartbeats_keep_a_device_online() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new(Retention {
+            max_events: 100,
+            max_age: None,
+        }));
+        let registry = registry(store.clone());
+
+        let first = registry.seen("speaker", report("1.0.0"), Some("10.0.0.2".to_string())).unwrap();
+        let second = registry.seen("speaker", DeviceReport::default(), None).unwrap();
+
+        assert!(second.online);
+        assert_eq!(second.registered_at, first.registered_at);
+        assert_eq!(second.model.as_deref(), Some("LX06"));
+        assert_eq!(second.version.as_deref(), Some("1.0.0"));
+        assert_eq!(second.ip.as_deref(), Some("10.0.0.2"));
+        assert!(registry.sweep().unwrap().is_empty());
+        assert_eq!(transitions(&store), ["device.online"]);
+    }
+
+    #[test]
+    fn silent_devices_go_offline_and_come_back() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new(Retention {
+            max_events: 100,
+            max_age: None,
+        }));
+        let registry = registry(store.clone());
+        let mut record = registry.seen("speaker", report("1.0.0"), None).unwrap();
+        record.last_seen -= 31_000;
+        store.upsert_device(&record).unwrap();
+
+        let offline = registry.sweep().unwrap();
+        assert_eq!(offline.len(), 1);
+        assert!(!registry.list().unwrap()[0].online);
+
+        registry.seen("speaker", report("1.0.1"), None).unwrap();
+        let devices = registry.list().unwrap();
+        assert!(devices[0].online);
+        assert_eq!(devices[0].version.as_deref(), Some("1.0.1"));
+        assert_eq!(
+            transitions(&store),
+            ["device.online", "device.offline", "device.online"]
+        );
+    }
+}
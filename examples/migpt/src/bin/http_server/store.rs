@@ -0,0 +1,693 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use open_xiaoai::services::config::{Validate, Validator};
+use open_xiaoai::services::connect::data::{Event, Response};
+
+use crate::registry::DeviceRecord;
+
+pub type StoreResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Default and maximum number of events returned by one query
+const DEFAULT_EVENT_LIMIT: usize = 100;
+const MAX_EVENT_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// SQLite database file; without it everything is kept in memory and lost on restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Only the most recent events are kept
+    #[serde(rename = "maxEvents", default = "StorageConfig::default_max_events")]
+    pub max_events: usize,
+    /// Events older than this are dropped, 0 keeps them until maxEvents is reached
+    #[serde(rename = "eventRetentionDays", default = "StorageConfig::default_retention_days")]
+    pub event_retention_days: u64,
+    /// Seconds an unacknowledged command stays deliverable, 0 keeps it until acknowledged
+    #[serde(rename = "commandTtl", default = "StorageConfig::default_command_ttl")]
+    pub command_ttl: u64,
+}
+
+impl StorageConfig {
+    fn default_max_events() -> usize {
+        10_000
+    }
+
+    fn default_retention_days() -> u64 {
+        7
+    }
+
+    fn default_command_ttl() -> u64 {
+        5 * 60
+    }
+
+    /// Expiry for a command queued now, unix milliseconds
+    pub fn command_expiry(&self) -> Option<i64> {
+        (self.command_ttl > 0)
+            .then(|| now() + self.command_ttl as i64 * 1000)
+    }
+
+    fn retention(&self) -> Retention {
+        Retention {
+            max_events: self.max_events,
+            max_age: (self.event_retention_days > 0)
+                .then(|| Duration::from_secs(self.event_retention_days * 24 * 60 * 60)),
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_events: StorageConfig::default_max_events(),
+            event_retention_days: StorageConfig::default_retention_days(),
+            command_ttl: StorageConfig::default_command_ttl(),
+        }
+    }
+}
+
+impl Validate for StorageConfig {
+    fn validate(&self, v: &mut Validator) {
+        if self.max_events == 0 {
+            v.error("storage.maxEvents", "must be greater than 0");
+        }
+        if let Some(path) = &self.path {
+            v.not_empty("storage.path", path);
+        }
+    }
+}
+
+/// How much event history to keep
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    pub max_events: usize,
+    pub max_age: Option<Duration>,
+}
+
+/// An event as recorded by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredEvent {
+    #[serde(rename = "clientId", skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Unix milliseconds when the server received the event
+    #[serde(rename = "receivedAt")]
+    pub received_at: i64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl StoredEvent {
+    pub fn received(event: Event) -> Self {
+        Self {
+            client_id: event
+                .data
+                .get("clientId")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            received_at: now(),
+            event,
+        }
+    }
+}
+
+/// Filters for GET /events
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventQuery {
+    #[serde(rename = "clientId")]
+    pub client_id: Option<String>,
+    pub name: Option<String>,
+    /// Only events received at or after this unix millisecond timestamp
+    pub since: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl EventQuery {
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_EVENT_LIMIT).min(MAX_EVENT_LIMIT)
+    }
+
+    fn matches(&self, event: &StoredEvent) -> bool {
+        self.client_id
+            .as_ref()
+            .is_none_or(|id| event.client_id.as_ref() == Some(id))
+            && self.name.as_ref().is_none_or(|name| &event.event.name == name)
+            && self.since.is_none_or(|since| event.received_at >= since)
+    }
+}
+
+/// Command queue, event history, device credentials and device records of the server
+pub trait Store: Send + Sync {
+    /// Queue a command for a device until it is acknowledged or `expires_at` (unix milliseconds)
+    /// passes. Queuing the same command id twice is a no-op.
+    fn push_command(&self, client_id: &str, command: &Response, expires_at: Option<i64>) -> StoreResult<()>;
+    /// Unexpired commands the device has not acknowledged yet, oldest first
+    fn pending_commands(&self, client_id: &str) -> StoreResult<Vec<Response>>;
+    /// Remove acknowledged commands, returns how many were removed
+    fn ack_commands(&self, client_id: &str, ids: &[String]) -> StoreResult<usize>;
+    /// Drop expired commands, returns how many were dropped
+    fn prune_commands(&self) -> StoreResult<usize>;
+    /// Record an event, dropping the oldest ones beyond maxEvents
+    fn append_event(&self, event: &StoredEvent) -> StoreResult<()>;
+    /// Most recent matching events, newest first
+    fn events(&self, query: &EventQuery) -> StoreResult<Vec<StoredEvent>>;
+    /// Drop events older than the retention period, returns how many were dropped
+    fn prune_events(&self) -> StoreResult<usize>;
+    /// Remember the secret issued to a device, replacing any earlier one
+    fn set_device_secret(&self, client_id: &str, secret: &str) -> StoreResult<()>;
+    /// The secret issued to a device, None if it never registered
+    fn device_secret(&self, client_id: &str) -> StoreResult<Option<String>>;
+    /// Insert or replace a device record
+    fn upsert_device(&self, record: &DeviceRecord) -> StoreResult<()>;
+    fn device(&self, client_id: &str) -> StoreResult<Option<DeviceRecord>>;
+    fn devices(&self) -> StoreResult<Vec<DeviceRecord>>;
+}
+
+/// SQLite when a path is configured, memory otherwise
+pub fn open(config: &StorageConfig) -> StoreResult<Arc<dyn Store>> {
+    let retention = config.retention();
+    match &config.path {
+        Some(path) => Ok(Arc::new(SqliteStore::open(path, retention)?)),
+        None => Ok(Arc::new(MemoryStore::new(retention))),
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn cutoff(retention: &Retention) -> Option<i64> {
+    retention
+        .max_age
+        .map(|age| now() - age.as_millis() as i64)
+}
+
+/// A queued command and when it expires
+type QueuedCommand = (Response, Option<i64>);
+
+/// Keeps everything in memory; used when no database is configured and in tests
+pub struct MemoryStore {
+    retention: Retention,
+    commands: Mutex<HashMap<String, Vec<QueuedCommand>>>,
+    events: Mutex<VecDeque<StoredEvent>>,
+    secrets: Mutex<HashMap<String, String>>,
+    devices: Mutex<HashMap<String, DeviceRecord>>,
+}
+
+impl MemoryStore {
+    pub fn new(retention: Retention) -> Self {
+        Self {
+            retention,
+            commands: Mutex::new(HashMap::new()),
+            events: Mutex::new(VecDeque::new()),
+            secrets: Mutex::new(HashMap::new()),
+            devices: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn push_command(&self, client_id: &str, command: &Response, expires_at: Option<i64>) -> StoreResult<()> {
+        let mut commands = self.commands.lock().unwrap();
+        let queue = commands.entry(client_id.to_string()).or_default();
+        if !queue.iter().any(|(queued, _)| queued.id == command.id) {
+            queue.push((command.clone(), expires_at));
+        }
+        Ok(())
+    }
+
+    fn pending_commands(&self, client_id: &str) -> StoreResult<Vec<Response>> {
+        let commands = self.commands.lock().unwrap();
+        let now = now();
+        Ok(commands
+            .get(client_id)
+            .into_iter()
+            .flatten()
+            .filter(|(_, expires_at)| expires_at.is_none_or(|at| at > now))
+            .map(|(command, _)| command.clone())
+            .collect())
+    }
+
+    fn ack_commands(&self, client_id: &str, ids: &[String]) -> StoreResult<usize> {
+        let mut commands = self.commands.lock().unwrap();
+        let Some(queue) = commands.get_mut(client_id) else {
+            return Ok(0);
+        };
+        let before = queue.len();
+        queue.retain(|(command, _)| !ids.contains(&command.id));
+        Ok(before - queue.len())
+    }
+
+    fn prune_commands(&self) -> StoreResult<usize> {
+        let mut commands = self.commands.lock().unwrap();
+        let now = now();
+        let mut pruned = 0;
+        for queue in commands.values_mut() {
+            let before = queue.len();
+            queue.retain(|(_, expires_at)| expires_at.is_none_or(|at| at > now));
+            pruned += before - queue.len();
+        }
+        commands.retain(|_, queue| !queue.is_empty());
+        Ok(pruned)
+    }
+
+    fn append_event(&self, event: &StoredEvent) -> StoreResult<()> {
+        let mut events = self.events.lock().unwrap();
+        events.push_back(event.clone());
+        while events.len() > self.retention.max_events {
+            events.pop_front();
+        }
+        Ok(())
+    }
+
+    fn events(&self, query: &EventQuery) -> StoreResult<Vec<StoredEvent>> {
+        let events = self.events.lock().unwrap();
+        Ok(events
+            .iter()
+            .rev()
+            .filter(|event| query.matches(event))
+            .take(query.limit())
+            .cloned()
+            .collect())
+    }
+
+    fn prune_events(&self) -> StoreResult<usize> {
+        let Some(cutoff) = cutoff(&self.retention) else {
+            return Ok(0);
+        };
+        let mut events = self.events.lock().unwrap();
+        let before = events.len();
+        events.retain(|event| event.received_at >= cutoff);
+        Ok(before - events.len())
+    }
+
+    fn set_device_secret(&self, client_id: &str, secret: &str) -> StoreResult<()> {
+        let mut secrets = self.secrets.lock().unwrap();
+        secrets.insert(client_id.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn device_secret(&self, client_id: &str) -> StoreResult<Option<String>> {
+        Ok(self.secrets.lock().unwrap().get(client_id).cloned())
+    }
+
+    fn upsert_device(&self, record: &DeviceRecord) -> StoreResult<()> {
+        let mut devices = self.devices.lock().unwrap();
+        devices.insert(record.client_id.clone(), record.clone());
+        Ok(())
+    }
+
+    fn device(&self, client_id: &str) -> StoreResult<Option<DeviceRecord>> {
+        Ok(self.devices.lock().unwrap().get(client_id).cloned())
+    }
+
+    fn devices(&self) -> StoreResult<Vec<DeviceRecord>> {
+        Ok(self.devices.lock().unwrap().values().cloned().collect())
+    }
+}
+
+/// Persists commands and events in a SQLite database
+pub struct SqliteStore {
+    retention: Retention,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str, retention: Retention) -> StoreResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn, retention)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory(retention: Retention) -> StoreResult<Self> {
+        Self::init(Connection::open_in_memory()?, retention)
+    }
+
+    fn init(conn: Connection, retention: Retention) -> StoreResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS commands (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                client_id TEXT NOT NULL,
+                id TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER,
+                UNIQUE (client_id, id)
+            );
+            CREATE TABLE IF NOT EXISTS events (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                client_id TEXT,
+                data TEXT NOT NULL,
+                received_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS events_received_at ON events (received_at);
+            CREATE TABLE IF NOT EXISTS devices (
+                client_id TEXT PRIMARY KEY,
+                secret TEXT NOT NULL,
+                registered_at INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS device_records (
+                client_id TEXT PRIMARY KEY,
+                data TEXT NOT NULL
+            );",
+        )?;
+        // Databases created before commands could expire
+        let has_expiry = conn
+            .prepare("SELECT 1 FROM pragma_table_info('commands') WHERE name = 'expires_at'")?
+            .exists([])?;
+        if !has_expiry {
+            conn.execute("ALTER TABLE commands ADD COLUMN expires_at INTEGER", [])?;
+        }
+        Ok(Self {
+            retention,
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl Store for SqliteStore {
+    fn push_command(&self, client_id: &str, command: &Response, expires_at: Option<i64>) -> StoreResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO commands (client_id, id, data, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                client_id,
+                command.id,
+                serde_json::to_string(&command.data)?,
+                now(),
+                expires_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn pending_commands(&self, client_id: &str) -> StoreResult<Vec<Response>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, data FROM commands
+             WHERE client_id = ?1 AND (expires_at IS NULL OR expires_at > ?2)
+             ORDER BY seq",
+        )?;
+        let rows = stmt.query_map(params![client_id, now()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut commands = Vec::new();
+        for row in rows {
+            let (id, data) = row?;
+            commands.push(Response::new(&id, serde_json::from_str(&data)?));
+        }
+        Ok(commands)
+    }
+
+    fn ack_commands(&self, client_id: &str, ids: &[String]) -> StoreResult<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut removed = 0;
+        {
+            let mut stmt = tx.prepare("DELETE FROM commands WHERE client_id = ?1 AND id = ?2")?;
+            for id in ids {
+                removed += stmt.execute(params![client_id, id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    fn prune_commands(&self) -> StoreResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM commands WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+            params![now()],
+        )?)
+    }
+
+    fn append_event(&self, event: &StoredEvent) -> StoreResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO events (id, name, client_id, data, received_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                event.event.id,
+                event.event.name,
+                event.client_id,
+                serde_json::to_string(&event.event.data)?,
+                event.received_at
+            ],
+        )?;
+        // seq only grows, so everything below the newest maxEvents rows can go
+        conn.execute(
+            "DELETE FROM events WHERE seq <= (SELECT MAX(seq) FROM events) - ?1",
+            params![self.retention.max_events as i64],
+        )?;
+        Ok(())
+    }
+
+    fn events(&self, query: &EventQuery) -> StoreResult<Vec<StoredEvent>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, client_id, data, received_at FROM events
+             WHERE (?1 IS NULL OR client_id = ?1)
+               AND (?2 IS NULL OR name = ?2)
+               AND (?3 IS NULL OR received_at >= ?3)
+             ORDER BY seq DESC LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![query.client_id, query.name, query.since, query.limit() as i64],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            },
+        )?;
+        let mut events = Vec::new();
+        for row in rows {
+            let (id, name, client_id, data, received_at) = row?;
+            events.push(StoredEvent {
+                client_id,
+                received_at,
+                event: Event {
+                    id,
+                    name,
+                    data: serde_json::from_str(&data)?,
+                },
+            });
+        }
+        Ok(events)
+    }
+
+    fn prune_events(&self) -> StoreResult<usize> {
+        let Some(cutoff) = cutoff(&self.retention) else {
+            return Ok(0);
+        };
+        let conn = self.conn.lock().unwrap();
+        Ok(conn.execute("DELETE FROM events WHERE received_at < ?1", params![cutoff])?)
+    }
+
+    fn set_device_secret(&self, client_id: &str, secret: &str) -> StoreResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO devices (client_id, secret, registered_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (client_id) DO UPDATE SET secret = excluded.secret, registered_at = excluded.registered_at",
+            params![client_id, secret, now()],
+        )?;
+        Ok(())
+    }
+
+    fn device_secret(&self, client_id: &str) -> StoreResult<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT secret FROM devices WHERE client_id = ?1")?;
+        let mut rows = stmt.query(params![client_id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn upsert_device(&self, record: &DeviceRecord) -> StoreResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO device_records (client_id, data) VALUES (?1, ?2)
+             ON CONFLICT (client_id) DO UPDATE SET data = excluded.data",
+            params![record.client_id, serde_json::to_string(record)?],
+        )?;
+        Ok(())
+    }
+
+    fn device(&self, client_id: &str) -> StoreResult<Option<DeviceRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM device_records WHERE client_id = ?1")?;
+        let mut rows = stmt.query(params![client_id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(serde_json::from_str(&row.get::<_, String>(0)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn devices(&self) -> StoreResult<Vec<DeviceRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT data FROM device_records")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut devices = Vec::new();
+        for row in rows {
+            devices.push(serde_json::from_str(&row?)?);
+        }
+        Ok(devices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn retention() -> Retention {
+        Retention {
+            max_events: 3,
+            max_age: Some(Duration::from_secs(60)),
+        }
+    }
+
+    fn stores() -> Vec<Box<dyn Store>> {
+        vec![
+            Box::new(MemoryStore::new(retention())),
+            Box::new(SqliteStore::open_in_memory(retention()).unwrap()),
+        ]
+    }
+
+    fn event(name: &str, client_id: &str, received_at: i64) -> StoredEvent {
+        StoredEvent {
+            client_id: Some(client_id.to_string()),
+            received_at,
+            event: Event::new(name, json!({ "clientId": client_id })),
+        }
+    }
+
+    #[test]
+    fn commands_stay_queued_until_acknowledged() {
+        for store in stores() {
+            let first = Response::new("1", json!({ "action": "tts" }));
+            let second = Response::new("2", json!({ "action": "pause" }));
+            store.push_command("speaker", &first, None).unwrap();
+            store.push_command("speaker", &second, None).unwrap();
+            store.push_command("speaker", &first, None).unwrap();
+
+            // Delivering a command does not remove it
+            assert_eq!(store.pending_commands("speaker").unwrap().len(), 2);
+
+            let pending = store.pending_commands("speaker").unwrap();
+            assert_eq!(pending.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), ["1", "2"]);
+            assert!(store.pending_commands("other").unwrap().is_empty());
+
+            assert_eq!(store.ack_commands("speaker", &["1".to_string()]).unwrap(), 1);
+            assert_eq!(store.ack_commands("other", &["2".to_string()]).unwrap(), 0);
+            let pending = store.pending_commands("speaker").unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].id, "2");
+            assert_eq!(pending[0].data, json!({ "action": "pause" }));
+        }
+    }
+
+    #[test]
+    fn expired_commands_are_not_delivered() {
+        let now = chrono::Utc::now().timestamp_millis();
+        for store in stores() {
+            let stale = Response::new("stale", json!({ "action": "tts" }));
+            let fresh = Response::new("fresh", json!({ "action": "tts" }));
+            store.push_command("speaker", &stale, Some(now - 1)).unwrap();
+            store.push_command("speaker", &fresh, Some(now + 60_000)).unwrap();
+
+            let pending = store.pending_commands("speaker").unwrap();
+            assert_eq!(pending.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), ["fresh"]);
+            assert_eq!(store.prune_commands().unwrap(), 1);
+            assert_eq!(store.ack_commands("speaker", &["stale".to_string()]).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn event_history_is_capped_and_filtered() {
+        let now = chrono::Utc::now().timestamp_millis();
+        for store in stores() {
+            for (i, name) in ["a", "b", "a", "b"].iter().enumerate() {
+                let client = if i % 2 == 0 { "x" } else { "y" };
+                store.append_event(&event(name, client, now + i as i64)).unwrap();
+            }
+
+            // Capped at 3: the first event is gone, newest first
+            let all = store.events(&EventQuery::default()).unwrap();
+            assert_eq!(all.iter().map(|e| e.received_at - now).collect::<Vec<_>>(), [3, 2, 1]);
+
+            let query = EventQuery {
+                name: Some("a".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(store.events(&query).unwrap().len(), 1);
+
+            let query = EventQuery {
+                client_id: Some("y".to_string()),
+                limit: Some(1),
+                ..Default::default()
+            };
+            let events = store.events(&query).unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].received_at, now + 3);
+        }
+    }
+
+    #[test]
+    fn device_secrets_are_replaced_on_registration() {
+        for store in stores() {
+            assert_eq!(store.device_secret("speaker").unwrap(), None);
+            store.set_device_secret("speaker", "first").unwrap();
+            store.set_device_secret("speaker", "second").unwrap();
+            assert_eq!(store.device_secret("speaker").unwrap().as_deref(), Some("second"));
+            assert_eq!(store.device_secret("other").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn device_records_are_upserted() {
+        for store in stores() {
+            let mut record = DeviceRecord {
+                client_id: "speaker".to_string(),
+                model: Some("LX06".to_string()),
+                serial: None,
+                version: Some("1.0.0".to_string()),
+                ip: None,
+                registered_at: 1,
+                last_seen: 1,
+                online: true,
+            };
+            store.upsert_device(&record).unwrap();
+            record.online = false;
+            store.upsert_device(&record).unwrap();
+
+            assert_eq!(store.device("speaker").unwrap(), Some(record.clone()));
+            assert_eq!(store.device("other").unwrap(), None);
+            assert_eq!(store.devices().unwrap(), [record]);
+        }
+    }
+
+    #[test]
+    fn prune_drops_events_past_retention() {
+        let now = chrono::Utc::now().timestamp_millis();
+        for store in stores() {
+            store.append_event(&event("old", "x", now - 120_000)).unwrap();
+            store.append_event(&event("new", "x", now)).unwrap();
+
+            assert_eq!(store.prune_events().unwrap(), 1);
+            let events = store.events(&EventQuery::default()).unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].event.name, "new");
+        }
+    }
+}
@@ -5,8 +5,11 @@ if [ -f .env ]; then
     export $(cat .env | grep -v '^#' | xargs)
 fi
 
-# Check required environment variables
-required_vars=("OPENAI_BASE_URL" "OPENAI_API_KEY" "OPENAI_MODEL" "SERVER_PROXY_URL")
+# Check required environment variables, the openai section is only used in direct mode
+required_vars=("SERVER_PROXY_URL" "SERVER_PROXY_TOKEN")
+if grep -q '"mode": *"direct"' config.template.json; then
+    required_vars+=("OPENAI_BASE_URL" "OPENAI_API_KEY" "OPENAI_MODEL")
+fi
 missing_vars=()
 
 for var in "${required_vars[@]}"; do
@@ -25,12 +28,15 @@ if [ ${#missing_vars[@]} -ne 0 ]; then
     exit 1
 fi
 
-# Generate config.json from template
+# Generate config.json from template, secrets stay as env: references resolved when the client loads
 echo "🔧 Generating config.json from template..."
-envsubst < config.template.json > config.json
+envsubst '${OPENAI_BASE_URL} ${OPENAI_MODEL} ${SERVER_PROXY_URL}' < config.template.json > config.json
 
 echo "✅ config.json generated successfully!"
 echo "📄 Using:"
-echo "   API Key: ${OPENAI_API_KEY:0:10}..."
-echo "   Model: ${OPENAI_MODEL}"
-echo "   Base URL: ${OPENAI_BASE_URL}"
+echo "   Server: ${SERVER_PROXY_URL}"
+if [ -n "${OPENAI_API_KEY}" ]; then
+    echo "   API Key: ${OPENAI_API_KEY:0:10}..."
+    echo "   Model: ${OPENAI_MODEL}"
+    echo "   Base URL: ${OPENAI_BASE_URL}"
+fi
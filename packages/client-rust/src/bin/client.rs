@@ -1,5 +1,4 @@
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
@@ -7,56 +6,35 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 use tokio::sync::Mutex;
-use uuid::Uuid;
 
+use open_xiaoai::services::boot::BootManager;
+use open_xiaoai::services::config::{self as config_loader, AuthScheme, Config, Mode, OpenAIConfig, ServerProxyConfig};
 use open_xiaoai::services::connect::data::{Event, Response};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Config {
-    mode: String, // "direct" or "proxy"
-    openai: OpenAIConfig,
-    #[serde(rename = "serverProxy")]
-    server_proxy: Option<ServerProxyConfig>,
-    prompt: PromptConfig,
-    audio: Option<AudioConfig>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct OpenAIConfig {
-    #[serde(rename = "baseURL")]
-    base_url: String,
-    #[serde(rename = "apiKey")]
-    api_key: String,
-    model: String,
-    timeout: Option<u64>,
-    #[serde(rename = "maxTokens")]
-    max_tokens: Option<u32>,
-    temperature: Option<f32>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ServerProxyConfig {
-    #[serde(rename = "baseURL")]
-    base_url: String,
-    timeout: Option<u64>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct PromptConfig {
-    system: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct AudioConfig {
-    #[serde(rename = "sampleRate")]
-    sample_rate: u32,
-    channels: u32,
-    format: String,
-}
+use open_xiaoai::services::auth;
+use open_xiaoai::services::identity::Identity;
+use open_xiaoai::services::llm::ProviderChain;
+use open_xiaoai::services::outbox::{HandledCommands, Outbox};
+use open_xiaoai::services::retry::{Backoff, CircuitBreaker};
+use open_xiaoai::services::command::{CommandOptions, CommandOutcome, DeviceCommand};
+use open_xiaoai::services::connect::rpc::RPC;
+use open_xiaoai::services::connect::message::FORWARDED_TOPICS;
+use open_xiaoai::services::gate::WakeGate;
+use open_xiaoai::services::interrupt::{InterruptReport, InterruptStrategy, Interrupter};
+use open_xiaoai::services::monitor::instruction::{InstructionMonitor, RecognizedInstruction, INSTRUCTION_RECOGNIZED};
+use open_xiaoai::services::monitor::kws::{KeywordDetected, KwsMonitor, KWS_KEYWORD, KWS_STARTED};
+use open_xiaoai::services::monitor::playing::PlayingMonitor;
+use open_xiaoai::services::router::{RouteAction, Router};
+use open_xiaoai::services::speaker::SpeakerManager;
+use open_xiaoai::services::speech::Speech;
+use open_xiaoai::services::status::StatusServer;
+use open_xiaoai::services::tools::SpeakerTools;
+use open_xiaoai::services::update::Updater;
+use open_xiaoai::utils::event::{EventBus, Subscription};
+use open_xiaoai::utils::task::{ChildState, Supervisor, TaskManager};
 
 pub enum LLMService {
     Direct(DirectLLMService),
-    Server(ServerProxyService),
+    Server(Box<ServerProxyService>),
 }
 
 impl LLMService {
@@ -71,29 +49,33 @@ impl LLMService {
 #[derive(Clone)]
 pub struct DirectLLMService {
     config: OpenAIConfig,
-    client: Client,
     system_prompt: String,
+    /// Tried in order until one answers; the primary provider comes first
+    providers: Arc<ProviderChain>,
 }
 
 impl DirectLLMService {
     pub fn new(config: OpenAIConfig, system_prompt: String) -> Self {
-        let timeout = Duration::from_secs(config.timeout.unwrap_or(30));
-        let client = Client::builder()
-            .timeout(timeout)
-            .build()
-            .expect("Failed to create HTTP client");
-
+        let providers = Arc::new(ProviderChain::new(&config));
         Self {
             config,
-            client,
             system_prompt,
+            providers,
         }
     }
 
+    /// Per-provider counters for the status endpoint
+    pub fn status(&self) -> Value {
+        self.providers.status()
+    }
+
     async fn call_llm(&self, instruction: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let url = format!("{}/chat/completions", self.config.base_url);
+        // Upper bound on tool call round trips for a single instruction
+        const MAX_TOOL_ROUNDS: usize = 4;
+
+        let use_tools = self.config.tools.unwrap_or(false);
         
-        let messages = vec![
+        let mut messages = vec![
             json!({
                 "role": "system",
                 "content": self.system_prompt
@@ -104,50 +86,115 @@ impl DirectLLMService {
             })
         ];
 
-        let body = json!({
-            "model": self.config.model,
-            "messages": messages,
-            "temperature": self.config.temperature.unwrap_or(0.7),
-            "max_tokens": self.config.max_tokens.unwrap_or(1000)
-        });
-
         println!("🤖 [DIRECT] Calling LLM: {}", instruction);
-        
-        let response = self.client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .json(&body)
-            .send()
-            .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(format!("LLM API error: {}", error_text).into());
-        }
+        for _ in 0..=MAX_TOOL_ROUNDS {
+            let mut body = json!({
+                "messages": messages,
+                "temperature": self.config.temperature.unwrap_or(0.7),
+                "max_tokens": self.config.max_tokens.unwrap_or(1000)
+            });
+            if use_tools {
+                body["tools"] = SpeakerTools::definitions();
+            }
 
-        let response_json: Value = response.json().await?;
-        
-        if let Some(choices) = response_json.get("choices").and_then(|c| c.as_array()) {
-            if let Some(first_choice) = choices.first() {
-                if let Some(message) = first_choice.get("message") {
-                    if let Some(content) = message.get("content").and_then(|c| c.as_str()) {
-                        println!("✅ [DIRECT] LLM response: {}", content);
-                        return Ok(content.to_string());
-                    }
-                }
+            let (response_json, provider) = self.providers.complete(body).await.map_err(|e| e.to_string())?;
+            let message = response_json
+                .get("choices")
+                .and_then(|c| c.as_array())
+                .and_then(|choices| choices.first())
+                .and_then(|choice| choice.get("message"))
+                .ok_or("Invalid LLM response format")?;
+
+            let tool_calls = message
+                .get("tool_calls")
+                .and_then(|t| t.as_array())
+                .filter(|calls| !calls.is_empty());
+            let Some(tool_calls) = tool_calls else {
+                let content = message
+                    .get("content")
+                    .and_then(|c| c.as_str())
+                    .ok_or("Invalid LLM response format")?;
+                println!("✅ [DIRECT] LLM response from {}: {}", provider, content);
+                return Ok(content.to_string());
+            };
+
+            // Run the requested tools and hand the results back to the model
+            messages.push(message.clone());
+            for call in tool_calls {
+                let id = call.get("id").and_then(|v| v.as_str()).unwrap_or_default();
+                let function = call.get("function");
+                let name = function.and_then(|f| f.get("name")).and_then(|v| v.as_str()).unwrap_or_default();
+                let arguments = function.and_then(|f| f.get("arguments")).and_then(|v| v.as_str()).unwrap_or_default();
+
+                println!("🔧 [DIRECT] Tool call: {}({})", name, arguments);
+                let result = SpeakerTools::call(name, arguments)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .unwrap_or_else(|e| json!({ "error": e }));
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": id,
+                    "content": result.to_string()
+                }));
             }
         }
 
-        Err("Invalid LLM response format".into())
+        Err("LLM kept calling tools without answering".into())
     }
 }
 
 pub struct ServerProxyService {
     config: ServerProxyConfig,
     client: Client,
-    client_id: String,
+    identity: Identity,
+    /// Resolved on first use, deriving it may need to ask the speaker for its serial number
+    client_id: tokio::sync::OnceCell<String>,
     headers: HashMap<String, String>,
+    handled: std::sync::Mutex<HandledCommands>,
+    /// Device secret issued by /register, used to authenticate every other request
+    secret: std::sync::Mutex<Option<String>>,
+    /// Set when the server rejects our credentials, e.g. after losing its device list
+    needs_register: std::sync::atomic::AtomicBool,
+    /// Set once a request has gone through, the health signal for boot verification
+    reached_server: std::sync::atomic::AtomicBool,
+    /// Events that could not be delivered, sent in order once the server is back
+    outbox: Outbox,
+    /// Serializes outbox flushes so a queued event is not sent twice
+    flushing: Mutex<()>,
+    /// Stops hammering a server that keeps failing
+    breaker: CircuitBreaker,
+}
+
+/// Non-success response from the server
+#[derive(Debug)]
+struct HttpError {
+    status: reqwest::StatusCode,
+    body: String,
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {} error: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl HttpError {
+    /// Status of a failed request, None if the server was never reached
+    fn status(error: &(dyn std::error::Error + Send + Sync + 'static)) -> Option<reqwest::StatusCode> {
+        error.downcast_ref::<HttpError>().map(|e| e.status)
+    }
+
+    /// Whether a request that failed this way is worth sending again later.
+    /// Other client errors mean the server will never accept it.
+    fn is_retryable(error: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+        match Self::status(error) {
+            None => true,
+            Some(status) => status.is_server_error() || status == reqwest::StatusCode::UNAUTHORIZED,
+        }
+    }
 }
 
 impl ServerProxyService {
@@ -168,15 +215,45 @@ impl ServerProxyService {
             headers.insert("CF-Access-Client-Secret".to_string(), client_secret);
         }
 
+        let outbox = Outbox::open(&config.offline.outbox_path, config.offline.outbox_size);
+        let handled = HandledCommands::open(
+            std::path::Path::new(&config.offline.outbox_path).with_file_name("handled-commands"),
+        );
+        if !outbox.is_empty() {
+            println!("📥 [PROXY] {} events waiting to be delivered", outbox.len());
+        }
+        let breaker = CircuitBreaker::new(
+            config.offline.failure_threshold,
+            Duration::from_secs(config.offline.cooldown),
+        );
+
         Self {
             config,
             client,
-            client_id: Uuid::new_v4().to_string(),
+            identity: Identity::default(),
+            client_id: tokio::sync::OnceCell::new(),
             headers,
+            handled: std::sync::Mutex::new(handled),
+            secret: std::sync::Mutex::new(None),
+            needs_register: std::sync::atomic::AtomicBool::new(false),
+            reached_server: std::sync::atomic::AtomicBool::new(false),
+            outbox,
+            flushing: Mutex::new(()),
+            breaker,
         }
     }
 
+    /// Stable id of this speaker, see Identity
+    async fn client_id(&self) -> &str {
+        self.client_id.get_or_init(|| self.identity.client_id()).await
+    }
+
     async fn send_request(&self, method: &str, path: &str, body: Option<Value>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        // While the breaker is open, fail fast instead of waiting for timeouts
+        if !self.breaker.allow() {
+            return Err("server unreachable, waiting before trying again".into());
+        }
+
         let url = format!("{}{}", self.config.base_url, path);
         
         let mut request = match method {
@@ -190,40 +267,190 @@ impl ServerProxyService {
             request = request.header(key, value);
         }
 
-        if let Some(body) = body {
-            request = request.header("Content-Type", "application/json").json(&body);
+        let body = match body {
+            Some(body) => {
+                request = request.header("Content-Type", "application/json");
+                serde_json::to_vec(&body)?
+            }
+            None => Vec::new(),
+        };
+
+        // Requests carry the device secret once we have one. Without it only /register can
+        // succeed, authenticated with the shared registration token.
+        let secret = self.secret.lock().unwrap().clone();
+        if let Some(secret) = secret {
+            request = request.header(auth::CLIENT_ID_HEADER, self.client_id().await);
+            match self.config.auth {
+                AuthScheme::Bearer => request = request.bearer_auth(secret),
+                AuthScheme::Hmac => {
+                    let timestamp = chrono::Utc::now().timestamp();
+                    let signature = auth::sign(&secret, method, path, timestamp, &body);
+                    request = request
+                        .header(auth::TIMESTAMP_HEADER, timestamp.to_string())
+                        .header(auth::SIGNATURE_HEADER, signature);
+                }
+            }
+        } else if path == "/register" {
+            if let Some(token) = &self.config.token {
+                request = request.bearer_auth(token);
+            }
+        }
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                self.record_failure();
+                return Err(e.into());
+            }
+        };
+        if response.status().is_server_error() {
+            self.record_failure();
+        } else {
+            self.breaker.record_success();
         }
 
-        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED && path != "/register" {
+            self.needs_register.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("HTTP {} error: {}", status, error_text).into());
+            return Err(HttpError { status, body: error_text }.into());
         }
 
         let result: Value = response.json().await?;
+        self.reached_server.store(true, std::sync::atomic::Ordering::Relaxed);
         Ok(result)
     }
 
+    /// Whether a request has succeeded since start
+    pub fn has_reached_server(&self) -> bool {
+        self.reached_server.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn record_failure(&self) {
+        if self.breaker.record_failure() {
+            eprintln!("🔌 [PROXY] Server unreachable, pausing requests for {}s", self.config.offline.cooldown);
+        }
+    }
+
+    /// Register with the server. A secret saved from an earlier registration is presented so
+    /// the server recognises a returning speaker; if it is refused we fall back to the
+    /// registration token and get a new one.
     pub async fn register(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let body = json!({
-            "clientId": self.client_id
+        let client_id = self.client_id().await.to_string();
+        let mut body = json!({
+            "clientId": client_id,
+            "version": open_xiaoai::base::VERSION
         });
+        match SpeakerManager::get_device_info().await.map_err(|e| e.to_string()) {
+            Ok(info) => body["device"] = json!(info),
+            Err(e) => eprintln!("⚠️  [PROXY] Failed to read device info: {}", e),
+        }
+
+        let saved = self.identity.secret();
+        let returning = saved.is_some();
+        *self.secret.lock().unwrap() = saved;
+        let response = match self.send_request("POST", "/register", Some(body.clone())).await {
+            Err(e) if returning && HttpError::status(&*e) == Some(reqwest::StatusCode::UNAUTHORIZED) => {
+                println!("🔑 [PROXY] Saved device secret was refused, registering with the token");
+                self.identity.forget_secret();
+                *self.secret.lock().unwrap() = None;
+                self.send_request("POST", "/register", Some(body)).await?
+            }
+            result => result?,
+        };
 
-        let _response = self.send_request("POST", "/register", Some(body)).await?;
-        println!("✅ [PROXY] Client registered: {}", self.client_id);
+        // Servers without auth don't issue a secret
+        let secret = response.get("secret").and_then(|v| v.as_str()).map(str::to_string);
+        match &secret {
+            Some(secret) if self.identity.secret().as_ref() != Some(secret) => {
+                if let Err(e) = self.identity.save_secret(secret) {
+                    eprintln!("⚠️  [PROXY] Failed to save device secret: {}", e);
+                }
+            }
+            _ => {}
+        }
+        *self.secret.lock().unwrap() = secret;
+        self.needs_register.store(false, std::sync::atomic::Ordering::Relaxed);
+        println!("✅ [PROXY] Client registered: {}", client_id);
         Ok(())
     }
 
+    /// Send an event. Events other than heartbeats are kept in the outbox when the server
+    /// can't take them and delivered in order later; an error is still returned so the
+    /// caller knows the event is not there yet.
     pub async fn send_event(&self, event: &Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let body = json!(event);
-        let _response = self.send_request("POST", "/events", Some(body)).await?;
+        // A heartbeat is stale by the time it could be retried
+        if event.name == "heartbeat" {
+            self.send_request("POST", "/events", Some(json!(event))).await?;
+            return Ok(());
+        }
+
+        // Queued events go first so the server sees them in order
+        if self.outbox.is_empty() {
+            match self.send_request("POST", "/events", Some(json!(event))).await {
+                Ok(_) => return Ok(()),
+                Err(e) if !HttpError::is_retryable(&*e) => return Err(e),
+                Err(e) => eprintln!("⚠️  [PROXY] Failed to send {} event: {}", event.name, e),
+            }
+        }
+
+        println!("📥 [PROXY] Queued {} event {} until the server is reachable", event.name, event.id);
+        if let Some(dropped) = self.outbox.push(event.clone()) {
+            eprintln!("🗑️  [PROXY] Outbox full, dropped {} event {}", dropped.name, dropped.id);
+        }
+        self.flush_outbox().await;
+        if self.outbox.contains(&event.id) {
+            return Err(format!("{} event queued until the server is reachable", event.name).into());
+        }
+        Ok(())
+    }
+
+    /// Forward an event from the local EventBus. Unlike instructions these are not queued:
+    /// a stale player state is of no use once the server is back.
+    pub async fn forward_event(&self, mut event: Event) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(data) = event.data.as_object_mut() {
+            data.insert("clientId".to_string(), json!(self.client_id().await));
+        }
+        self.send_request("POST", "/events", Some(json!(event))).await?;
         Ok(())
     }
 
+    /// Deliver queued events oldest first, stopping at the first one the server can't take yet
+    async fn flush_outbox(&self) {
+        let _guard = self.flushing.lock().await;
+        let mut delivered = 0;
+        while let Some(event) = self.outbox.front() {
+            match self.send_request("POST", "/events", Some(json!(event))).await {
+                Ok(_) => delivered += 1,
+                Err(e) if !HttpError::is_retryable(&*e) => {
+                    eprintln!("🗑️  [PROXY] Server refused queued {} event {}: {}", event.name, event.id, e);
+                }
+                Err(_) => break,
+            }
+            self.outbox.remove(&event.id);
+        }
+        if delivered > 0 {
+            println!("📤 [PROXY] Delivered {} queued events", delivered);
+        }
+    }
+
+    /// What to say when the server can't be reached, or the error if it is configured to stay silent
+    fn fallback_reply(&self, error: Box<dyn std::error::Error + Send + Sync>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let message = &self.config.offline.fallback_message;
+        if message.is_empty() {
+            return Err(error);
+        }
+        Ok(message.clone())
+    }
+
     pub async fn poll_commands(&self) -> Result<Vec<Response>, Box<dyn std::error::Error + Send + Sync>> {
-        let path = format!("/commands/{}", self.client_id);
+        let path = format!("/commands/{}", self.client_id().await);
         let response = self.send_request("GET", &path, None).await?;
         
         if let Some(commands) = response.get("commands") {
@@ -237,37 +464,105 @@ impl ServerProxyService {
         }
     }
 
+    /// Tell the server these commands were handled so it stops redelivering them.
+    /// Results of executed commands travel with the ack.
+    pub async fn ack_commands(&self, ids: &[String], results: &[CommandOutcome]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if ids.is_empty() && results.is_empty() {
+            return Ok(());
+        }
+        let path = format!("/commands/{}/ack", self.client_id().await);
+        let body = json!({ "ids": ids, "results": results });
+        self.send_request("POST", &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// Poll for commands, skipping ones already handled.
+    /// Redelivered duplicates are acknowledged again since their first ack may have been lost.
+    async fn poll_new_commands(&self) -> Result<Vec<Response>, Box<dyn std::error::Error + Send + Sync>> {
+        let commands = self.poll_commands().await?;
+        let (duplicates, fresh): (Vec<Response>, Vec<Response>) = {
+            let handled = self.handled.lock().unwrap();
+            commands.into_iter().partition(|command| handled.contains(&command.id))
+        };
+        if !duplicates.is_empty() {
+            let ids: Vec<String> = duplicates.into_iter().map(|command| command.id).collect();
+            println!("🔁 [PROXY] Skipping {} already handled commands", ids.len());
+            if let Err(e) = self.ack_commands(&ids, &[]).await {
+                eprintln!("⚠️  [PROXY] Failed to acknowledge commands: {}", e);
+            }
+        }
+        Ok(fresh)
+    }
+
+    /// Record a command as handled and acknowledge it. Returns false for a duplicate.
+    async fn complete_command(&self, command: &Response, outcome: Option<CommandOutcome>) -> bool {
+        if !self.handled.lock().unwrap().insert(&command.id) {
+            return false;
+        }
+        let results: Vec<CommandOutcome> = outcome.into_iter().collect();
+        if let Err(e) = self.ack_commands(std::slice::from_ref(&command.id), &results).await {
+            // The server will redeliver it; the handled set makes that a no-op
+            eprintln!("⚠️  [PROXY] Failed to acknowledge command {}: {}", command.id, e);
+        }
+        true
+    }
+
+    /// Run a server command on the speaker. Unknown actions are reported back as failures.
+    async fn execute_command(&self, command: &Response) -> CommandOutcome {
+        let options = CommandOptions {
+            allow_shell: self.config.allow_shell,
+        };
+        let result = match DeviceCommand::parse(&command.data) {
+            Ok(device_command) => device_command.execute(&options).await,
+            Err(e) => Err(format!("unsupported command: {}", e).into()),
+        };
+        CommandOutcome::from_result(&command.id, result)
+    }
+
     async fn call_llm(&self, instruction: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         println!("🌐 [PROXY] Sending instruction to server: {}", instruction);
         
         // Send instruction event to server
         let event = Event::new("instruction", json!({
             "text": instruction,
-            "clientId": self.client_id
+            "clientId": self.client_id().await
         }));
         
-        self.send_event(&event).await?;
+        if let Err(e) = self.send_event(&event).await {
+            // A queued instruction is still answered once it gets through;
+            // the main loop speaks the reply when it arrives
+            if self.outbox.contains(&event.id) {
+                eprintln!("❌ [PROXY] {}", e);
+                return self.fallback_reply(e);
+            }
+            return Err(e);
+        }
         println!("✅ [PROXY] Instruction sent, waiting for response...");
         
         // Poll for response (try for up to 30 seconds)
         for attempt in 1..=30 {
             sleep(Duration::from_secs(1)).await;
             
-            match self.poll_commands().await {
+            match self.poll_new_commands().await {
                 Ok(commands) => {
+                    // Other commands stay queued for the main loop
                     for command in commands {
-                        if let Some(action) = command.data.get("action") {
-                            if action == "tts" {
-                                if let Some(text) = command.data.get("text").and_then(|v| v.as_str()) {
-                                    println!("🎯 [PROXY] Received response: {}", text);
-                                    return Ok(text.to_string());
-                                }
+                        if command.data.get("action").and_then(|v| v.as_str()) != Some("tts") {
+                            continue;
+                        }
+                        if let Some(text) = command.data.get("text").and_then(|v| v.as_str()) {
+                            if self.complete_command(&command, None).await {
+                                println!("🎯 [PROXY] Received response: {}", text);
+                                return Ok(text.to_string());
                             }
                         }
                     }
                 }
                 Err(e) => {
                     println!("⚠️  [PROXY] Poll attempt {} failed: {}", attempt, e);
+                    if self.breaker.is_open() {
+                        return self.fallback_reply(e);
+                    }
                 }
             }
         }
@@ -276,87 +571,306 @@ impl ServerProxyService {
     }
 
     pub async fn run_proxy_mode(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Poll every 5 seconds, backing off while the server is failing
+        const POLL_INTERVAL: Duration = Duration::from_secs(5);
+        let mut backoff = Backoff::new(POLL_INTERVAL, Duration::from_secs(60));
+
         // Register with server
-        if let Err(e) = self.register().await {
-            eprintln!("❌ [PROXY] Failed to register: {}", e);
-            return Err(e);
+        while let Err(e) = self.register().await {
+            let delay = backoff.next_delay();
+            eprintln!("❌ [PROXY] Failed to register: {} (retrying in {}s)", e, delay.as_secs());
+            sleep(delay).await;
         }
+        backoff.reset();
 
         println!("🔄 [PROXY] Starting main loop...");
+
+        // Device info is gathered once a minute and piggybacks on the heartbeat
+        const DEVICE_INFO_INTERVAL: Duration = Duration::from_secs(60);
+        let mut last_device_info: Option<std::time::Instant> = None;
         
         loop {
-            // Poll for commands every 5 seconds
-            match self.poll_commands().await {
+            if self.needs_register.load(std::sync::atomic::Ordering::Relaxed) {
+                println!("🔑 [PROXY] Server rejected our credentials, registering again...");
+                if let Err(e) = self.register().await {
+                    eprintln!("❌ [PROXY] Failed to register: {}", e);
+                }
+            }
+
+            let reachable = match self.poll_new_commands().await {
                 Ok(commands) => {
                     for command in commands {
                         println!("📋 [PROXY] Processing command: {:?}", command.data);
-                        // Process commands here if needed
+                        let outcome = self.execute_command(&command).await;
+                        if !outcome.ok {
+                            eprintln!("⚠️  [PROXY] Command {} failed: {}", command.id, outcome.error.as_deref().unwrap_or("unknown error"));
+                        }
+                        self.complete_command(&command, Some(outcome)).await;
                     }
+                    true
                 }
                 Err(e) => {
                     eprintln!("❌ [PROXY] Failed to poll commands: {}", e);
+                    false
                 }
+            };
+
+            if reachable && !self.outbox.is_empty() {
+                self.flush_outbox().await;
             }
 
             // Send a heartbeat event
-            let heartbeat = Event::new("heartbeat", json!({
+            let mut payload = json!({
                 "timestamp": chrono::Utc::now().timestamp(),
-                "clientId": self.client_id
-            }));
+                "clientId": self.client_id().await,
+                "version": open_xiaoai::base::VERSION
+            });
+            if last_device_info.is_none_or(|at| at.elapsed() >= DEVICE_INFO_INTERVAL) {
+                match SpeakerManager::get_device_info().await.map_err(|e| e.to_string()) {
+                    Ok(info) => {
+                        payload["device"] = json!(info);
+                        last_device_info = Some(std::time::Instant::now());
+                    }
+                    Err(e) => eprintln!("⚠️  [PROXY] Failed to read device info: {}", e),
+                }
+            }
+            let heartbeat = Event::new("heartbeat", payload);
 
             if let Err(e) = self.send_event(&heartbeat).await {
                 eprintln!("❌ [PROXY] Failed to send heartbeat: {}", e);
             }
 
-            sleep(Duration::from_secs(5)).await;
+            let delay = if reachable {
+                backoff.reset();
+                POLL_INTERVAL
+            } else {
+                backoff.next_delay()
+            };
+            sleep(delay).await;
         }
     }
 }
 
-pub struct MultiModeClient {
-    llm_service: LLMService,
+/// Everything derived from one version of the config file.
+/// Dialogs hold on to the snapshot they started with, so a reload never changes
+/// the backend or prompt under an in-flight dialog.
+pub struct Runtime {
     config: Config,
+    llm_service: Arc<LLMService>,
+    router: Arc<Router>,
+    /// Holds the rest of a reply that was cut short, until the user asks for it
+    speech: Arc<Speech>,
 }
 
-impl MultiModeClient {
-    pub fn new(config_path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let config_content = fs::read_to_string(config_path)?;
-        let config: Config = serde_json::from_str(&config_content)?;
+impl Runtime {
+    /// Parse and validate a config file without touching the running client.
+    /// Services whose config section is unchanged are carried over from `current` with their
+    /// state: the proxy's outbox, handled commands and device secret, provider usage and a
+    /// reply waiting to be continued.
+    fn load(config_path: &str, current: Option<&Runtime>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let config: Config = config_loader::load(config_path)?;
+
+        let llm_service = match current {
+            Some(current) if current.config.same_llm_config(&config) => Arc::clone(&current.llm_service),
+            _ => Arc::new(Self::llm_service(&config)?),
+        };
+
+        // Already validated by the loader
+        let router = Router::new(config.router.clone())
+            .map_err(|e| format!("Invalid router config: {}", e))?;
+
+        let speech = match current {
+            Some(current) if config_loader::same_section(&current.config.speech, &config.speech) => Arc::clone(&current.speech),
+            _ => Arc::new(Speech::new(config.speech.clone())),
+        };
+
+        Ok(Self {
+            config,
+            llm_service,
+            router: Arc::new(router),
+            speech,
+        })
+    }
 
-        let llm_service = match config.mode.as_str() {
-            "direct" => {
-                LLMService::Direct(DirectLLMService::new(
-                    config.openai.clone(), 
-                    config.prompt.system.clone()
-                ))
+    fn llm_service(config: &Config) -> Result<LLMService, Box<dyn std::error::Error + Send + Sync>> {
+        match config.mode {
+            Mode::Direct => {
+                let openai = config.openai.clone()
+                    .ok_or("openai config missing for direct mode")?;
+                Ok(LLMService::Direct(DirectLLMService::new(openai, config.prompt.system.clone())))
             }
-            "proxy" => {
+            Mode::Proxy => {
                 let server_config = config.server_proxy.as_ref()
                     .ok_or("Server proxy config missing for proxy mode")?;
-                LLMService::Server(ServerProxyService::new(server_config.clone()))
+                Ok(LLMService::Server(Box::new(ServerProxyService::new(server_config.clone()))))
+            }
+        }
+    }
+}
+
+/// Config sections that need the production pipeline (monitors or proxy loop) to be rebuilt
+const PIPELINE_SECTIONS: [&str; 3] = ["mode", "serverProxy", "gate"];
+/// Config sections that are only read at startup
+const STARTUP_SECTIONS: [&str; 2] = ["status", "update"];
+
+pub struct MultiModeClient {
+    config_path: String,
+    runtime: Arc<std::sync::RwLock<Arc<Runtime>>>,
+    updater: Option<Updater>,
+    /// Signalled when a reload needs the production pipeline to restart
+    pipeline_changed: tokio::sync::Notify,
+}
+
+impl MultiModeClient {
+    pub fn new(config_path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let runtime = Runtime::load(config_path, None)?;
+        let config = &runtime.config;
+
+        let updater = match &config.update {
+            Some(update) => {
+                let proxy_url = config.server_proxy.as_ref().map(|proxy| proxy.base_url.as_str());
+                let updater = Updater::from_config(update, proxy_url)
+                    .map_err(|e| format!("Invalid update config: {}", e))?;
+                Some(updater)
             }
-            _ => return Err(format!("Unknown mode: {}. Valid options: 'direct', 'proxy'", config.mode).into()),
+            None => None,
         };
 
         Ok(Self {
-            llm_service,
-            config,
+            config_path: config_path.to_string(),
+            runtime: Arc::new(std::sync::RwLock::new(Arc::new(runtime))),
+            updater,
+            pipeline_changed: tokio::sync::Notify::new(),
         })
     }
 
-    pub async fn process_instruction(&self, text: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        // Extract instruction from text (simplified)
-        let instruction = if text.starts_with("请") || text.starts_with("你") {
-            text
-        } else {
-            return Ok("Not a valid instruction".to_string());
+    /// The config snapshot currently in effect
+    pub fn runtime(&self) -> Arc<Runtime> {
+        Arc::clone(&self.runtime.read().unwrap())
+    }
+
+    /// Re-read the config file and swap it in if it is valid.
+    /// Returns the list of changes; an invalid file leaves the running config untouched.
+    pub fn reload_config(&self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let next = Runtime::load(&self.config_path, Some(&self.runtime()))
+            .map_err(|e| format!("{} (keeping the current config)", e))?;
+
+        let mut runtime = self.runtime.write().unwrap();
+        let mut changes = Vec::new();
+        config_diff(
+            "",
+            &serde_json::to_value(&runtime.config)?,
+            &serde_json::to_value(&next.config)?,
+            &mut changes,
+        );
+        if changes.is_empty() {
+            return Ok(changes);
+        }
+
+        let section = |change: &String| change.split(['.', ':']).next().unwrap_or_default().to_string();
+        let restart_pipeline = changes.iter().any(|c| PIPELINE_SECTIONS.contains(&section(c).as_str()));
+        let needs_restart = changes.iter().any(|c| STARTUP_SECTIONS.contains(&section(c).as_str()));
+
+        *runtime = Arc::new(next);
+        drop(runtime);
+
+        println!("🔧 Config reloaded from {}:", self.config_path);
+        for change in &changes {
+            println!("   • {}", change);
+        }
+        if needs_restart {
+            println!("⚠️  Changes to {} take effect after the client restarts", STARTUP_SECTIONS.join("/"));
+        }
+        if restart_pipeline {
+            self.pipeline_changed.notify_one();
+        }
+        Ok(changes)
+    }
+
+    /// Reload the config when the file changes or on SIGHUP
+    pub async fn watch_config(&self) {
+        // How often the config file's modification time is checked
+        const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+        let modified = || fs::metadata(&self.config_path).and_then(|m| m.modified()).ok();
+        let mut last_modified = modified();
+
+        #[cfg(unix)]
+        let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .map_err(|e| eprintln!("⚠️  Failed to listen for SIGHUP: {}", e))
+            .ok();
+
+        loop {
+            #[cfg(unix)]
+            let hangup = async {
+                match sighup.as_mut() {
+                    Some(sighup) => sighup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = hangup => println!("🔧 Received SIGHUP, reloading config..."),
+                _ = sleep(WATCH_INTERVAL) => {
+                    let current = modified();
+                    if current == last_modified {
+                        continue;
+                    }
+                    last_modified = current;
+                    println!("🔧 Config file changed, reloading...");
+                }
+            }
+
+            match self.reload_config() {
+                Ok(changes) if changes.is_empty() => println!("🔧 Config unchanged"),
+                Ok(_) => {}
+                Err(e) => eprintln!("❌ {}", e),
+            }
+        }
+    }
+
+    /// Periodically check for a newer client release.
+    /// Returns the installed version once an update has been swapped in; never returns otherwise.
+    pub async fn run_updater(&self) -> String {
+        let Some(updater) = &self.updater else {
+            return std::future::pending().await;
         };
 
-        self.llm_service.call_llm(instruction).await
+        // Give the client a minute to settle before the first check
+        sleep(Duration::from_secs(60)).await;
+        loop {
+            match updater.update().await.map_err(|e| e.to_string()) {
+                Ok(Some(version)) => {
+                    println!("⬆️  Installed client {} (previous binary kept at {})", version, updater.previous_binary().display());
+                    return version;
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("⚠️  Update check failed: {}", e),
+            }
+            // The interval follows config reloads; the update source does not
+            let interval = self.runtime().config.update.as_ref()
+                .map(|update| update.check_interval)
+                .unwrap_or(6 * 60 * 60);
+            sleep(Duration::from_secs(interval.max(60))).await;
+        }
+    }
+
+    pub async fn process_instruction(&self, text: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let runtime = self.runtime();
+        let route = runtime.router.route(text);
+        println!("🧭 Route: {} -> {:?}", route.rule.unwrap_or("default"), route.action);
+
+        let reply = Self::run_route_action(route.action, route.rule, text, &runtime.llm_service).await?;
+        Ok(match reply {
+            Some(reply) => runtime.speech.speak(&reply),
+            None => "Handled without a spoken reply".to_string(),
+        })
     }
 
     pub async fn run_test_loop(&self) {
-        println!("🚀 Multi-Mode Client starting in {} mode", self.config.mode);
+        println!("🚀 Multi-Mode Client starting in {} mode", self.runtime().config.mode);
 
         let test_instruction = "你好，请介绍一下自己";
         println!("\n📝 Testing: {}", test_instruction);
@@ -376,34 +890,129 @@ impl MultiModeClient {
     }
     
     pub async fn run_production_mode_with_debug(&self, debug: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        match &self.llm_service {
-            LLMService::Server(proxy_service) => {
-                println!("🚀 Starting proxy mode production client...");
-                if debug {
-                    println!("🐛 Debug: Proxy service configuration loaded");
+        if let Some(status) = &self.runtime().config.status {
+            let addr = status.addr().map_err(|e| e.to_string())?;
+            StatusServer::instance()
+                .register("supervisor", || async { json!(Supervisor::instance().health().await) })
+                .await;
+            StatusServer::instance()
+                .register("tasks", || async { json!(TaskManager::instance().snapshot().await) })
+                .await;
+            StatusServer::instance()
+                .register("boot", || async {
+                    match BootManager::instance().status().await {
+                        Ok(status) => json!(status),
+                        Err(e) => json!({ "error": e.to_string() }),
+                    }
+                })
+                .await;
+            StatusServer::instance()
+                .register("device", || async {
+                    match SpeakerManager::get_device_info().await {
+                        Ok(info) => json!(info),
+                        Err(e) => json!({ "error": e.to_string() }),
+                    }
+                })
+                .await;
+            StatusServer::instance().serve(addr).await;
+            println!("📊 Status endpoint listening on http://{}/status", addr);
+        }
+
+        // After an A/B switch, confirm the new partition once the client does its job: the
+        // server answered in proxy mode, the monitors are running in direct mode.
+        // Otherwise BootManager rolls back to the previous partition.
+        let runtime = Arc::clone(&self.runtime);
+        TaskManager::instance()
+            .spawn("BootVerify", |token| async move {
+                let verify = async {
+                    BootManager::instance().verify(|| {
+                        let runtime = Arc::clone(&runtime.read().unwrap());
+                        async move {
+                            match runtime.llm_service.as_ref() {
+                                LLMService::Server(proxy_service) => proxy_service.has_reached_server(),
+                                LLMService::Direct(_) => Supervisor::instance().is_healthy().await,
+                            }
+                        }
+                    })
+                    .await
+                    .map_err(|e| e.to_string())
+                };
+                tokio::select! {
+                    // Shutting down before the deadline: verification resumes on next start
+                    _ = token.cancelled() => {}
+                    result = verify => {
+                        if let Err(e) = result {
+                            eprintln!("❌ Boot verification failed: {}", e);
+                        }
+                    }
                 }
-                proxy_service.run_proxy_mode().await?;
-            }
-            LLMService::Direct(direct_service) => {
-                println!("🚀 Starting direct mode production client...");
-                if debug {
-                    println!("🐛 Debug: Direct LLM service configuration loaded");
+            })
+            .await;
+
+        // Player state is published on the EventBus in either mode
+        PlayingMonitor::start().await;
+
+        // Rebuild the pipeline whenever a config reload changes its mode, proxy or gate settings.
+        // Dialogs already running keep going on the config they started with.
+        loop {
+            let runtime = self.runtime();
+            let pipeline = async {
+                match runtime.llm_service.as_ref() {
+                    LLMService::Server(proxy_service) => {
+                        println!("🚀 Starting proxy mode production client...");
+                        if debug {
+                            println!("🐛 Debug: Proxy service configuration loaded");
+                        }
+                        let _forwarding = Self::forward_events_to_server(Arc::clone(&runtime.llm_service)).await;
+                        proxy_service.run_proxy_mode().await
+                    }
+                    LLMService::Direct(_) => {
+                        println!("🚀 Starting direct mode production client...");
+                        if debug {
+                            println!("🐛 Debug: Direct LLM service configuration loaded");
+                        }
+                        self.run_direct_mode_production_with_debug(&runtime.config, debug).await
+                    }
+                }
+            };
+            tokio::select! {
+                result = pipeline => return result,
+                _ = self.pipeline_changed.notified() => {
+                    println!("🔄 Restarting {} mode pipeline to apply the new config", runtime.config.mode);
                 }
-                self.run_direct_mode_production_with_debug(direct_service, debug).await?;
+            }
+            // The direct mode monitors outlive its pipeline; stop them so a switch to proxy mode
+            // does not leave them running. Direct mode starts them again.
+            if runtime.config.mode == Mode::Direct {
+                KwsMonitor::stop().await;
+                InstructionMonitor::stop().await;
             }
         }
-        Ok(())
     }
 
-    async fn run_direct_mode_production(&self, direct_service: &DirectLLMService) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.run_direct_mode_production_with_debug(direct_service, false).await
+    /// The server is the peer in proxy mode: it gets the same EventBus topics a connected
+    /// peer would, for as long as the returned subscriptions are kept.
+    async fn forward_events_to_server(llm_service: Arc<LLMService>) -> Vec<Subscription> {
+        let mut subscriptions = Vec::new();
+        for topic in FORWARDED_TOPICS {
+            let llm_service = Arc::clone(&llm_service);
+            let subscription = EventBus::instance()
+                .subscribe(topic, move |event| {
+                    let llm_service = Arc::clone(&llm_service);
+                    async move {
+                        if let LLMService::Server(proxy_service) = llm_service.as_ref() {
+                            proxy_service.forward_event(event).await.map_err(|e| e.to_string())?;
+                        }
+                        Ok(())
+                    }
+                })
+                .await;
+            subscriptions.push(subscription);
+        }
+        subscriptions
     }
 
-    async fn run_direct_mode_production_with_debug(&self, direct_service: &DirectLLMService, debug: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        use open_xiaoai::services::monitor::instruction::{InstructionMonitor, LogMessage, Payload};
-        use open_xiaoai::services::monitor::kws::{KwsMonitor, KwsMonitorEvent};
-        use open_xiaoai::services::monitor::file::FileMonitorEvent;
-        use std::sync::atomic::{AtomicBool, Ordering};
+    async fn run_direct_mode_production_with_debug(&self, config: &Config, debug: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         
         println!("🎤 Direct mode: Integrating with XiaoAi device audio system");
         
@@ -415,12 +1024,12 @@ impl MultiModeClient {
         let kws_dir = "/tmp/open-xiaoai";
         let kws_file = "/tmp/open-xiaoai/kws.log";
         
-        if let Err(_) = std::fs::metadata(kws_dir) {
+        if std::fs::metadata(kws_dir).is_err() {
             std::fs::create_dir_all(kws_dir)?;
             println!("📁 Created wake word directory: {}", kws_dir);
         }
         
-        if let Err(_) = std::fs::metadata(kws_file) {
+        if std::fs::metadata(kws_file).is_err() {
             std::fs::write(kws_file, "")?;
             println!("📄 Created wake word log file: {}", kws_file);
         }
@@ -430,237 +1039,99 @@ impl MultiModeClient {
         
         if debug {
             println!("🐛 Debug: File monitoring setup complete");
-            println!("🐛 Debug: Setting up wake word detection state");
+            println!("🐛 Debug: Setting up wake word gate ({:?})", config.gate);
         }
         
-        // Shared state to track if wake word was detected
-        let wake_detected = Arc::new(AtomicBool::new(false));
-        let wake_detected_clone = Arc::clone(&wake_detected);
+        // Wake word gate deciding which utterances we handle
+        let gate = Arc::new(WakeGate::new(config.gate.clone()));
+        {
+            let gate = Arc::clone(&gate);
+            StatusServer::instance()
+                .register("gate", move || {
+                    let gate = Arc::clone(&gate);
+                    async move { json!(gate.status().await) }
+                })
+                .await;
+        }
         
-        // Start monitoring wake words and instructions in parallel
-        let direct_service_clone = Arc::new(direct_service.clone());
-        let wake_detected_for_instruction = Arc::clone(&wake_detected);
+        {
+            let runtime = Arc::clone(&self.runtime);
+            StatusServer::instance()
+                .register("llm", move || {
+                    let runtime = Arc::clone(&runtime.read().unwrap());
+                    async move {
+                        match runtime.llm_service.as_ref() {
+                            LLMService::Direct(service) => service.status(),
+                            LLMService::Server(_) => Value::Null,
+                        }
+                    }
+                })
+                .await;
+        }
         
-        // Spawn wake word monitoring in background
-        let wake_task = {
-            let wake_detected = Arc::clone(&wake_detected_clone);
+        // Subscribe before the monitors start so no early event is missed.
+        // The subscriptions live as long as this function keeps running.
+        let _kws_started = EventBus::instance()
+            .subscribe_typed(&KWS_STARTED, |_timestamp| async {
+                println!("🎤 Wake word monitoring started");
+                Ok(())
+            })
+            .await;
+
+        let _kws_keyword = {
+            let gate = Arc::clone(&gate);
             let debug_flag = debug;
-            tokio::spawn(async move {
-                if debug_flag {
-                    println!("🐛 Debug: Wake word monitoring task starting");
-                }
-                
-                // Make wake word monitoring more resilient
-                loop {
-                    if debug_flag {
-                        println!("🐛 Debug: Starting wake word monitor");
-                    }
-                    
-                    let wake_detected = Arc::clone(&wake_detected);
-                    let debug_flag = debug_flag;
-                    
-                    KwsMonitor::start(move |event| {
-                        let wake_detected = Arc::clone(&wake_detected);
-                        let debug_flag = debug_flag;
-                        async move {
+            EventBus::instance()
+                .subscribe_typed(&KWS_KEYWORD, move |detected: KeywordDetected| {
+                    let gate = Arc::clone(&gate);
+                    async move {
+                        println!("🎯 Wake word detected: {}", detected.keyword);
+                        if gate.on_keyword(&detected.keyword).await {
                             if debug_flag {
-                                println!("🐛 Debug: Wake word event: {:?}", event);
-                            }
-                            
-                            match event {
-                                KwsMonitorEvent::Keyword(keyword) => {
-                                    println!("🎯 Wake word detected: {}", keyword);
-                                    wake_detected.store(true, Ordering::Relaxed);
-                                    
-                                    // Reset wake word detection after 10 seconds
-                                    let wake_detected_reset = Arc::clone(&wake_detected);
-                                    tokio::spawn(async move {
-                                        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-                                        wake_detected_reset.store(false, Ordering::Relaxed);
-                                        if debug_flag {
-                                            println!("🐛 Debug: Wake word detection reset after timeout");
-                                        }
-                                    });
-                                }
-                                KwsMonitorEvent::Started => {
-                                    println!("🎤 Wake word monitoring started");
-                                }
+                                println!("🐛 Debug: Wake gate opened for {}s", gate.config().wake_window);
                             }
-                            Ok(())
+                        } else if debug_flag {
+                            println!("🐛 Debug: Wake word '{}' does not open the gate", detected.keyword);
                         }
-                    }).await;
-                    
-                    if debug_flag {
-                        println!("🐛 Debug: Wake word monitoring stopped, restarting in 30 seconds...");
+                        Ok(())
                     }
-                    
-                    // Wait before restarting
-                    tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-                }
-            })
+                })
+                .await
         };
-        
-        // Spawn instruction monitoring in background
-        let instruction_task = {
-            let direct_service = Arc::clone(&direct_service_clone);
-            let wake_detected = Arc::clone(&wake_detected_for_instruction);
+
+        let _instruction_recognized = {
+            let runtime = Arc::clone(&self.runtime);
+            let gate = Arc::clone(&gate);
             let debug_flag = debug;
-            
-            tokio::spawn(async move {
-                if debug_flag {
-                    println!("🐛 Debug: Instruction monitoring task starting");
-                }
-                
-                // Make instruction monitoring more resilient
-                loop {
-                    if debug_flag {
-                        println!("🐛 Debug: Starting instruction monitor");
+            EventBus::instance()
+                .subscribe_typed(&INSTRUCTION_RECOGNIZED, move |instruction: RecognizedInstruction| {
+                    // Each dialog runs on the config that was current when it was recognized
+                    let runtime = Arc::clone(&runtime.read().unwrap());
+                    let gate = Arc::clone(&gate);
+                    async move {
+                        let router = Arc::clone(&runtime.router);
+                        let llm_service = Arc::clone(&runtime.llm_service);
+                        let speech = Arc::clone(&runtime.speech);
+                        Self::spawn_dialog(instruction, router, llm_service, speech, gate, debug_flag).await;
+                        Ok(())
                     }
-                    
-                    let direct_service = Arc::clone(&direct_service);
-                    let wake_detected = Arc::clone(&wake_detected);
-                    let debug_flag = debug_flag;
-                    
-                    InstructionMonitor::start(move |event| {
-                        let direct_service = Arc::clone(&direct_service);
-                        let wake_detected = Arc::clone(&wake_detected);
-                        let debug_flag = debug_flag;
-                        
-                        async move {
-                            if let FileMonitorEvent::NewLine(content) = event {
-                                if debug_flag {
-                                    println!("🐛 Debug: New instruction line: {}", content);
-                                }
-                                
-                                // Parse the instruction log line
-                                if let Ok(log_message) = serde_json::from_str::<LogMessage>(&content) {
-                                    if let Payload::RecognizeResultPayload { is_final, results, .. } = log_message.payload {
-                                        if is_final && !results.is_empty() {
-                                            let text = &results[0].text;
-                                            // Lower the confidence threshold and accept any non-empty text
-                                            if !text.trim().is_empty() {
-                                                // Deduplication: Check if we've processed this instruction recently
-                                                static LAST_INSTRUCTION: std::sync::OnceLock<Arc<Mutex<Option<(String, u64)>>>> = std::sync::OnceLock::new();
-                                                let last_instruction = LAST_INSTRUCTION.get_or_init(|| Arc::new(Mutex::new(None)));
-                                                
-                                                let current_time = std::time::SystemTime::now()
-                                                    .duration_since(std::time::UNIX_EPOCH)
-                                                    .unwrap_or_default()
-                                                    .as_secs();
-                                                
-                                                let mut last_processed = last_instruction.lock().await;
-                                                let should_skip = if let Some((last_text, last_time)) = &*last_processed {
-                                                    last_text == text && current_time - last_time < 3
-                                                } else {
-                                                    false
-                                                };
-                                                
-                                                if should_skip {
-                                                    if debug_flag {
-                                                        println!("🐛 Debug: Skipping duplicate instruction '{}' (processed recently)", text);
-                                                    }
-                                                    return Ok(());
-                                                }
-                                                
-                                                // Update the last processed instruction
-                                                *last_processed = Some((text.to_string(), current_time));
-                                                drop(last_processed);
-                                                
-                                                println!("🎤 Voice instruction: '{}' (confidence: {})", text, results[0].confidence);
-                                                
-                                                if debug_flag {
-                                                    println!("🐛 Debug: Processing voice instruction (confidence threshold relaxed)");
-                                                }
-                                                
-                                                // For now, process ALL voice instructions to bypass wake word requirement
-                                                // TODO: Add proper wake word detection later
-                                                let should_process = true; // wake_detected.load(Ordering::Relaxed) || true;
-                                                
-                                                if should_process {
-                                                    println!("✅ Processing voice instruction");
-                                                    
-                                                    // First, interrupt XiaoAi's default processing
-                                                    if debug_flag {
-                                                        println!("🐛 Debug: Interrupting XiaoAi default processing");
-                                                    }
-                                                    
-                                                    if let Err(e) = Self::interrupt_xiaoai().await {
-                                                        if debug_flag {
-                                                            println!("🐛 Debug: Failed to interrupt XiaoAi: {}", e);
-                                                        }
-                                                    }
-                                                    
-                                                    if debug_flag {
-                                                        println!("🐛 Debug: Calling LLM with text: '{}'", text);
-                                                    }
-                                                    
-                                                    // Process the instruction with LLM
-                                                    match direct_service.call_llm(text).await {
-                                                        Ok(response) => {
-                                                            println!("🤖 LLM Response: {}", response);
-                                                            
-                                                            if debug_flag {
-                                                                println!("🐛 Debug: Sending TTS response: '{}'", response);
-                                                            }
-                                                            
-                                                            // Send response to device TTS
-                                                            if let Err(e) = Self::send_tts_response(&response).await {
-                                                                eprintln!("❌ Failed to send TTS response: {}", e);
-                                                                if debug_flag {
-                                                                    eprintln!("🐛 Debug: TTS error details: {:?}", e);
-                                                                }
-                                                            } else if debug_flag {
-                                                                println!("🐛 Debug: TTS response sent successfully");
-                                                            }
-                                                            
-                                                            // Reset wake word detection after processing
-                                                            wake_detected.store(false, Ordering::Relaxed);
-                                                            if debug_flag {
-                                                                println!("🐛 Debug: Wake word detection reset after processing");
-                                                            }
-                                                        }
-                                                        Err(e) => {
-                                                            eprintln!("❌ LLM call failed: {}", e);
-                                                            if debug_flag {
-                                                                eprintln!("🐛 Debug: LLM error details: {:?}", e);
-                                                            }
-                                                        }
-                                                    }
-                                                } else {
-                                                    println!("⏭️  Ignoring instruction (no recent wake word detected)");
-                                                    if debug_flag {
-                                                        println!("🐛 Debug: Instruction ignored - wake word not detected recently");
-                                                    }
-                                                }
-                                            } else if debug_flag {
-                                                println!("🐛 Debug: Skipping empty text");
-                                            }
-                                        }
-                                    }
-                                } else if debug_flag {
-                                    println!("🐛 Debug: Failed to parse JSON: {}", content);
-                                }
-                            }
-                            Ok(())
-                        }
-                    }).await;
-                    
-                    if debug_flag {
-                        println!("🐛 Debug: Instruction monitoring stopped, restarting in 30 seconds...");
-                    }
-                    
-                    // Wait before restarting
-                    tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-                }
-            })
-        };        println!("✅ Audio monitoring started - waiting for wake words and instructions");
+                })
+                .await
+        };
+
+        // Monitors only publish onto the EventBus; the supervisor restarts them when they exit or fail.
+        // A panic aborts the whole process in release builds (panic = "abort").
+        KwsMonitor::start().await;
+        InstructionMonitor::start().await;
+
+        println!("✅ Audio monitoring started - waiting for wake words and instructions");
         
         if debug {
             println!("🐛 Debug: Both monitoring tasks spawned");
             println!("🐛 Debug: Entering main service loop - the client will run until manually stopped");
         }
         
-        // Keep the service running - the tasks run in background
+        // Keep the service running - the supervised tasks run in background
         // We use a simple infinite loop with periodic heartbeat
         let mut heartbeat_counter = 0;
         loop {
@@ -671,55 +1142,280 @@ impl MultiModeClient {
                 println!("🐛 Debug: Heartbeat #{} - service running normally", heartbeat_counter);
             }
             
-            // Check if tasks are still running
-            if wake_task.is_finished() {
-                eprintln!("❌ Wake word monitoring task has stopped unexpectedly");
-                return Err("Wake word monitoring failed".into());
-            }
-            
-            if instruction_task.is_finished() {
-                eprintln!("❌ Instruction monitoring task has stopped unexpectedly");
-                return Err("Instruction monitoring failed".into());
+            // Report monitors that are currently down; the supervisor restarts them
+            for child in Supervisor::instance().health().await {
+                if child.state != ChildState::Running {
+                    eprintln!(
+                        "⚠️  Monitor '{}' is down (restarts: {}, last error: {})",
+                        child.name,
+                        child.restarts,
+                        child.last_error.as_deref().unwrap_or("none")
+                    );
+                } else if debug {
+                    println!("🐛 Debug: Monitor '{}' healthy (restarts: {})", child.name, child.restarts);
+                }
             }
         }
     }
 
-    async fn interrupt_xiaoai() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        use std::process::Command;
-        
-        // Interrupt XiaoAi's default processing by restarting the mico service
-        let output = Command::new("sh")
-            .arg("-c")
-            .arg("/etc/init.d/mico_aivs_lab restart >/dev/null 2>&1")
-            .output();
-            
-        match output {
-            Ok(result) => {
-                if result.status.success() {
-                    println!("🛑 XiaoAi default processing interrupted");
-                } else {
-                    println!("⚠️  Failed to interrupt XiaoAi service");
+    /// Queue an instruction for the "dialog" worker. A single worker handles them one at a time
+    /// in the order they were recognized (TaskManager::spawn_queue); it is a tracked task so
+    /// shutdown lets the current one finish.
+    async fn spawn_dialog(
+        instruction: RecognizedInstruction,
+        router: Arc<Router>,
+        llm_service: Arc<LLMService>,
+        speech: Arc<Speech>,
+        gate: Arc<WakeGate>,
+        debug: bool,
+    ) {
+        type Dialog = (RecognizedInstruction, Arc<Router>, Arc<LLMService>, Arc<Speech>, Arc<WakeGate>, bool);
+        static DIALOGS: tokio::sync::OnceCell<tokio::sync::mpsc::UnboundedSender<Dialog>> = tokio::sync::OnceCell::const_new();
+
+        let dialogs = DIALOGS
+            .get_or_init(|| {
+                TaskManager::instance().spawn_queue("dialog", |dialog: Dialog| async move {
+                    let (instruction, router, llm_service, speech, gate, debug) = dialog;
+                    Self::handle_instruction(&instruction, &router, &llm_service, &speech, &gate, debug).await;
+                })
+            })
+            .await;
+
+        if dialogs.send((instruction, router, llm_service, speech, gate, debug)).is_err() && debug {
+            println!("🐛 Debug: Dropping instruction received during shutdown");
+        }
+    }
+
+    /// Process a final recognition result published by the instruction monitor
+    async fn handle_instruction(
+        instruction: &RecognizedInstruction,
+        router: &Router,
+        llm_service: &LLMService,
+        speech: &Speech,
+        gate: &WakeGate,
+        debug: bool,
+    ) {
+        let text = &instruction.text;
+        if debug {
+            println!("🐛 Debug: Recognized instruction: {:?}", instruction);
+        }
+
+        // Deduplication: Check if we've processed this instruction recently
+        type LastInstruction = Arc<Mutex<Option<(String, u64)>>>;
+        static LAST_INSTRUCTION: std::sync::OnceLock<LastInstruction> = std::sync::OnceLock::new();
+        let last_instruction = LAST_INSTRUCTION.get_or_init(|| Arc::new(Mutex::new(None)));
+
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut last_processed = last_instruction.lock().await;
+        let should_skip = if let Some((last_text, last_time)) = &*last_processed {
+            last_text == text && current_time - last_time < 3
+        } else {
+            false
+        };
+
+        if should_skip {
+            if debug {
+                println!("🐛 Debug: Skipping duplicate instruction '{}' (processed recently)", text);
+            }
+            return;
+        }
+
+        // Update the last processed instruction
+        *last_processed = Some((text.to_string(), current_time));
+        drop(last_processed);
+
+        println!("🎤 Voice instruction: '{}' (confidence: {})", text, instruction.confidence);
+
+        if !gate.allow().await {
+            println!("⏭️  Ignoring instruction (no recent wake word detected)");
+            if debug {
+                println!("🐛 Debug: Instruction ignored - wake gate is closed");
+            }
+            return;
+        }
+
+        println!("✅ Processing voice instruction");
+        let replied = Self::dispatch_instruction(router, llm_service, speech, text, debug).await;
+
+        // Keep listening for a follow-up question once we finished speaking
+        if replied {
+            match gate.follow_up(SpeakerManager::rearm).await {
+                Ok(true) if debug => {
+                    println!("🐛 Debug: Follow-up window open for {}s", gate.config().follow_up_window);
                 }
+                Ok(_) => {}
+                Err(e) => eprintln!("⚠️  Failed to re-arm XiaoAi for follow-up: {}", e),
             }
+        }
+    }
+
+    /// Route a recognised utterance and carry out the matching action.
+    /// Returns whether a reply was spoken.
+    async fn dispatch_instruction(router: &Router, llm_service: &LLMService, speech: &Speech, text: &str, debug: bool) -> bool {
+        // "Continue" after a reply that was cut short speaks the next part
+        if let Some(rest) = speech.resume(text) {
+            println!("⏩ Continuing the previous reply");
+            return Self::speak_reply(&rest, debug).await;
+        }
+
+        let route = router.route(text);
+        println!("🧭 Route: {} -> {:?}", route.rule.unwrap_or("default"), route.action);
+
+        if let RouteAction::Xiaoai = route.action {
+            println!("⏭️  Leaving instruction to XiaoAi");
+            return false;
+        }
+
+        let interrupt_report = if route.interrupt {
+            if debug {
+                println!("🐛 Debug: Interrupting XiaoAi default processing");
+            }
+            let report = Self::interrupt_xiaoai().await;
+            if debug {
+                println!("🐛 Debug: Interrupt attempts: {}", report);
+            }
+            Some(report)
+        } else {
+            None
+        };
+
+        let reply = Self::run_route_action(route.action, route.rule, text, llm_service).await;
+
+        // Undo interrupt side effects (e.g. muted output) before speaking
+        if let Some(report) = &interrupt_report {
+            if let Err(e) = Interrupter::restore(report).await {
+                eprintln!("⚠️  Failed to restore output after interrupt: {}", e);
+            }
+        }
+
+        match reply {
+            Ok(Some(response)) => {
+                println!("🤖 Response: {}", response);
+                Self::speak_reply(&speech.speak(&response), debug).await
+            }
+            Ok(None) => false,
             Err(e) => {
-                println!("⚠️  XiaoAi interruption command failed: {}", e);
+                eprintln!("❌ Instruction handling failed: {}", e);
+                if debug {
+                    eprintln!("🐛 Debug: Error details: {:?}", e);
+                }
+                false
             }
         }
-        
-        // Small delay to allow service restart
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-        
-        Ok(())
+    }
+
+    /// Send a reply to device TTS. Returns whether it was spoken.
+    async fn speak_reply(text: &str, debug: bool) -> bool {
+        if debug {
+            println!("🐛 Debug: Sending TTS response: '{}'", text);
+        }
+
+        if let Err(e) = Self::send_tts_response(text).await {
+            eprintln!("❌ Failed to send TTS response: {}", e);
+            if debug {
+                eprintln!("🐛 Debug: TTS error details: {:?}", e);
+            }
+            return false;
+        }
+        if debug {
+            println!("🐛 Debug: TTS response sent successfully");
+        }
+        true
+    }
+
+    /// Carry out a route action, returning the reply that should be spoken (if any)
+    async fn run_route_action(
+        action: &RouteAction,
+        rule: Option<&str>,
+        text: &str,
+        llm_service: &LLMService,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        match action {
+            RouteAction::Xiaoai => Ok(None),
+            RouteAction::Llm => llm_service.call_llm(text).await.map(Some),
+            RouteAction::Speaker { command } => {
+                let ok = command.execute().await.map_err(|e| e.to_string())?;
+                println!("🔈 Speaker action {:?}: {}", command, if ok { "ok" } else { "failed" });
+                Ok(None)
+            }
+            RouteAction::Webhook { url, timeout } => {
+                Self::call_webhook(url, *timeout, rule, text).await
+            }
+        }
+    }
+
+    async fn call_webhook(
+        url: &str,
+        timeout: Option<u64>,
+        rule: Option<&str>,
+        text: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(timeout.unwrap_or(10)))
+            .build()?;
+
+        println!("🪝 Calling webhook: {}", url);
+        let response = client
+            .post(url)
+            .json(&json!({
+                "text": text,
+                "rule": rule,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Webhook returned HTTP {}", response.status()).into());
+        }
+
+        // An empty or non-JSON body simply means there is nothing to say
+        let body: Value = response.json().await.unwrap_or(Value::Null);
+        Ok(body.get("reply").and_then(|v| v.as_str()).map(|v| v.to_string()))
+    }
+
+    async fn interrupt_xiaoai() -> InterruptReport {
+        // Try the cheap strategies first; restarting mico_aivs_lab is only the last resort
+        let report = Interrupter::interrupt().await;
+
+        match report.strategy() {
+            Some(strategy) => {
+                println!("🛑 XiaoAi default processing interrupted via {} ({})", strategy.name(), report);
+            }
+            None => {
+                println!("⚠️  Failed to interrupt XiaoAi service ({})", report);
+            }
+        }
+
+        // Only a service restart needs time to settle
+        if report.strategy() == Some(InterruptStrategy::RestartService) {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        }
+
+        report
     }
 
     async fn send_tts_response(text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         use std::process::Command;
         
+        // Speak at the assistant volume if one is configured
+        let previous_volume = SpeakerManager::begin_assistant_voice().await.unwrap_or_else(|e| {
+            eprintln!("⚠️  Failed to switch to assistant volume: {}", e);
+            None
+        });
+
         // Use device TTS system
         let output = Command::new("sh")
             .arg("-c")
-            .arg(&format!("/usr/sbin/tts_play.sh '{}'", text.replace("'", "'\\''")))
+            .arg(format!("/usr/sbin/tts_play.sh '{}'", text.replace("'", "'\\''")))
             .output();
+
+        if let Err(e) = SpeakerManager::end_assistant_voice(previous_volume).await {
+            eprintln!("⚠️  Failed to restore media volume: {}", e);
+        }
             
         match output {
             Ok(result) => {
@@ -753,6 +1449,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     
     for (i, arg) in args.iter().enumerate() {
         match arg.as_str() {
+            "--schema" => {
+                // JSON Schema of config.json, for editors and CI checks
+                println!("{}", serde_json::to_string_pretty(&Config::schema())?);
+                return Ok(());
+            }
             "--test" => test_mode = true,
             "--debug" => debug_mode = true,
             _ if i == 1 => config_path = Some(arg.clone()),
@@ -778,7 +1479,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         println!("🔧 Arguments: test_mode={}, debug_mode={}", test_mode, debug_mode);
     }
 
-    let client = MultiModeClient::new(&config_path)?;
+    // The client runs on the speaker itself, so speaker actions execute locally
+    SpeakerManager::use_local_shell(true);
+    SpeakerManager::register_commands().await;
+    BootManager::register_commands().await;
+
+    let client = match MultiModeClient::new(&config_path) {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            // Returning the error from main would print its Debug form
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Reached through the server's reload_config and rpc commands
+    {
+        let client = Arc::clone(&client);
+        RPC::instance()
+            .add_command("reload_config", move |_| {
+                let client = Arc::clone(&client);
+                async move {
+                    let changes = client.reload_config().map_err(|e| e.to_string())?;
+                    Ok(Response::from_data(json!({ "changes": changes })))
+                }
+            })
+            .await;
+    }
     
     if test_mode {
         println!("🧪 Running in test mode");
@@ -788,12 +1514,107 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         if debug_mode {
             println!("🐛 Debug: Starting production mode with detailed logging");
         }
-        client.run_production_mode_with_debug(debug_mode).await?;
+        tokio::select! {
+            result = client.run_production_mode_with_debug(debug_mode) => result?,
+            _ = client.watch_config() => {}
+            signal = wait_for_shutdown_signal() => {
+                println!("🛑 Received {}, shutting down gracefully...", signal);
+                shutdown(debug_mode).await;
+            }
+            version = client.run_updater() => {
+                println!("🔁 Restarting into client {}...", version);
+                shutdown(debug_mode).await;
+                if let Some(updater) = &client.updater {
+                    let e = updater.reexec();
+                    eprintln!("❌ Failed to restart into the new binary: {}", e);
+                    return Err(e.to_string().into());
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Collect the leaf values that differ between two configs as "path: old -> new".
+/// Credentials are masked so the log never contains them.
+fn config_diff(path: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+    if let (Value::Object(old), Value::Object(new)) = (old, new) {
+        let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+            config_diff(
+                &child,
+                old.get(key).unwrap_or(&Value::Null),
+                new.get(key).unwrap_or(&Value::Null),
+                changes,
+            );
+        }
+        return;
+    }
+    if old == new {
+        return;
+    }
+    let field = path.rsplit('.').next().unwrap_or_default().to_ascii_lowercase();
+    let secret = ["key", "secret", "token", "password"].iter().any(|s| field.contains(s));
+    let show = |value: &Value| if secret && !value.is_null() { "***".to_string() } else { value.to_string() };
+    changes.push(format!("{}: {} -> {}", path, show(old), show(new)));
+}
+
+/// How long in-flight dialogs get to finish before they are aborted
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn wait_for_shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => "SIGTERM",
+                    _ = tokio::signal::ctrl_c() => "SIGINT",
+                }
+            }
+            Err(e) => {
+                eprintln!("⚠️  Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+async fn shutdown(debug: bool) {
+    // Stop the monitors first so no new dialogs get started
+    Supervisor::instance().stop_all().await;
+    StatusServer::instance().stop().await;
+
+    if debug {
+        for task in TaskManager::instance().snapshot().await {
+            println!("🐛 Debug: Pending task '{}' x{} (oldest {}ms)", task.tag, task.count, task.oldest_age_ms);
+        }
+    }
+
+    let report = TaskManager::instance().shutdown(SHUTDOWN_TIMEOUT).await;
+    if report.aborted > 0 {
+        eprintln!(
+            "⚠️  {} task(s) did not finish within {}s and were aborted ({} finished)",
+            report.aborted,
+            SHUTDOWN_TIMEOUT.as_secs(),
+            report.drained
+        );
+    } else {
+        println!("✅ Shutdown complete ({} task(s) finished)", report.drained);
+    }
+}
+
 fn print_usage() {
     println!("🤖 Open-XiaoAi Unified Client");
     println!();
@@ -809,6 +1630,7 @@ fn print_usage() {
     println!("  ./client config.json --test       # Run in test mode");
     println!("  ./client config.json --debug      # Run with debug logging");
     println!("  ./client config.json --test --debug  # Test mode with debug");
+    println!("  ./client --schema                 # Print the config JSON Schema");
     println!();
     println!("📖 Configuration Setup:");
     println!();
@@ -829,7 +1651,7 @@ fn print_usage() {
     println!("  \"mode\": \"direct\",");
     println!("  \"openai\": {{");
     println!("    \"baseURL\": \"https://api.openai.com/v1\",");
-    println!("    \"apiKey\": \"your-api-key\",");
+    println!("    \"apiKey\": \"env:OPENAI_API_KEY\",");
     println!("    \"model\": \"gpt-4\",");
     println!("    \"timeout\": 30,");
     println!("    \"maxTokens\": 1000,");
@@ -845,4 +1667,6 @@ fn print_usage() {
     println!("  • Use direct mode for standalone operation");
     println!("  • Copy config.template.json and modify for your setup");
     println!("  • Test your config with --test flag first");
+    println!("  • Keep secrets out of the file: API keys and the server token may be \"env:NAME\" or \"file:/path\"");
+    println!("  • Edits to the config file are applied live (also on SIGHUP or the server's reload_config command)");
 }
@@ -0,0 +1,69 @@
+use sha2::{Digest, Sha256};
+
+/// 设备 id
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+/// 签名时间，unix 秒
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+/// hex 编码的 HMAC-SHA256 签名
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// 签名时间和服务器时间最多相差的秒数
+pub const MAX_CLOCK_SKEW: i64 = 5 * 60;
+
+const BLOCK_SIZE: usize = 64;
+
+/// HMAC-SHA256（RFC 2104）
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// 请求签名，签名内容为 `METHOD\nPATH\nTIMESTAMP\nSHA256(BODY)`
+pub fn sign(secret: &str, method: &str, path: &str, timestamp: i64, body: &[u8]) -> String {
+    let message = format!(
+        "{}\n{}\n{}\n{}",
+        method.to_ascii_uppercase(),
+        path,
+        timestamp,
+        hex::encode(Sha256::digest(body))
+    );
+    hex::encode(hmac_sha256(secret.as_bytes(), message.as_bytes()))
+}
+
+/// 校验请求签名和签名时间
+pub fn verify(
+    secret: &str,
+    method: &str,
+    path: &str,
+    timestamp: i64,
+    body: &[u8],
+    signature: &str,
+    now: i64,
+) -> bool {
+    if (now - timestamp).abs() > MAX_CLOCK_SKEW {
+        return false;
+    }
+    let expected = sign(secret, method, path, timestamp, body);
+    constant_time_eq(expected.as_bytes(), signature.trim().as_bytes())
+}
+
+/// 比较密钥时使用，耗时与内容无关
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
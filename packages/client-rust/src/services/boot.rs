@@ -0,0 +1,512 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock};
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::base::AppError;
+use crate::services::connect::{data::Response, rpc::RPC};
+use crate::services::speaker::SpeakerManager;
+
+/// A/B 启动分区
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootPartition {
+    Boot0,
+    Boot1,
+}
+
+impl BootPartition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootPartition::Boot0 => "boot0",
+            BootPartition::Boot1 => "boot1",
+        }
+    }
+
+    /// 另一个分区
+    pub fn other(&self) -> Self {
+        match self {
+            BootPartition::Boot0 => BootPartition::Boot1,
+            BootPartition::Boot1 => BootPartition::Boot0,
+        }
+    }
+}
+
+impl fmt::Display for BootPartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BootPartition {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "boot0" => Ok(BootPartition::Boot0),
+            "boot1" => Ok(BootPartition::Boot1),
+            other => Err(format!("invalid boot partition: {:?}", other).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BootState {
+    /// 已切换，等待新分区上的 client 确认健康
+    Pending,
+    /// 新分区已确认健康
+    Confirmed,
+    /// 超时未确认，已切回原分区
+    RolledBack,
+}
+
+/// 最近一次切换分区的记录，保存在两个分区共享的 /data 上
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootRecord {
+    pub previous: BootPartition,
+    pub target: BootPartition,
+    pub state: BootState,
+    /// 切换时间，unix 秒
+    pub switched_at: i64,
+    /// 重启后必须在这个时间内确认健康，否则回滚
+    pub confirm_timeout_secs: u64,
+    /// 第一次从新分区启动的时间，unix 秒，client 重启时不会重新计时
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub booted_at: Option<i64>,
+}
+
+impl BootRecord {
+    /// 确认的截止时间，unix 秒
+    pub fn deadline(&self) -> i64 {
+        let started = self
+            .booted_at
+            .unwrap_or(self.switched_at)
+            .max(self.switched_at);
+        started.saturating_add(self.confirm_timeout_secs as i64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootStatus {
+    pub current: BootPartition,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<BootRecord>,
+}
+
+pub static BOOT_RECORD_PATH: &str = "/data/open-xiaoai/boot.json";
+
+/// 健康检查的间隔
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 分区管理用到的设备操作，测试时可以替换
+pub trait BootDevice: Send + Sync {
+    /// 当前的启动分区
+    fn current(&self) -> BoxFuture<'_, Result<BootPartition, AppError>>;
+    /// 设置下次启动的分区
+    fn set_boot(&self, partition: BootPartition) -> BoxFuture<'_, Result<bool, AppError>>;
+    fn reboot(&self) -> BoxFuture<'_, Result<(), AppError>>;
+    /// 本次开机的时间，unix 秒
+    fn booted_at(&self) -> BoxFuture<'_, Result<i64, AppError>>;
+}
+
+/// 音箱本机的分区操作
+pub struct SpeakerBoot;
+
+impl BootDevice for SpeakerBoot {
+    fn current(&self) -> BoxFuture<'_, Result<BootPartition, AppError>> {
+        Box::pin(async { SpeakerManager::get_boot().await?.parse() })
+    }
+
+    fn set_boot(&self, partition: BootPartition) -> BoxFuture<'_, Result<bool, AppError>> {
+        Box::pin(SpeakerManager::set_boot(partition.as_str()))
+    }
+
+    fn reboot(&self) -> BoxFuture<'_, Result<(), AppError>> {
+        Box::pin(SpeakerManager::reboot())
+    }
+
+    fn booted_at(&self) -> BoxFuture<'_, Result<i64, AppError>> {
+        Box::pin(async {
+            let uptime = tokio::fs::read_to_string("/proc/uptime").await?;
+            let uptime = uptime
+                .split_whitespace()
+                .next()
+                .and_then(|secs| secs.parse::<f64>().ok())
+                .ok_or("invalid /proc/uptime")?;
+            Ok(chrono::Utc::now().timestamp() - uptime as i64)
+        })
+    }
+}
+
+/// 管理 A/B 分区切换：切换前记录原分区，重启后等待确认，超时自动回滚
+///
+/// 需要运行在音箱本机上，回滚由新分区上启动的 client 负责，client 完全起不来时不会回滚
+pub struct BootManager {
+    record_path: PathBuf,
+    device: Arc<dyn BootDevice>,
+}
+
+static INSTANCE: LazyLock<BootManager> =
+    LazyLock::new(|| BootManager::new(BOOT_RECORD_PATH, Arc::new(SpeakerBoot)));
+
+impl BootManager {
+    pub fn new(record_path: impl Into<PathBuf>, device: Arc<dyn BootDevice>) -> Self {
+        Self {
+            record_path: record_path.into(),
+            device,
+        }
+    }
+
+    pub fn instance() -> &'static Self {
+        &INSTANCE
+    }
+
+    /// 当前的启动分区
+    pub async fn current(&self) -> Result<BootPartition, AppError> {
+        self.device.current().await
+    }
+
+    pub async fn status(&self) -> Result<BootStatus, AppError> {
+        let current = self.current().await?;
+        Ok(BootStatus {
+            current,
+            record: self.record().await,
+        })
+    }
+
+    /// 最近一次切换的记录
+    pub async fn record(&self) -> Option<BootRecord> {
+        let data = tokio::fs::read(&self.record_path).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// 切换到目标分区，下次重启生效
+    pub async fn switch_to(
+        &self,
+        target: BootPartition,
+        confirm_timeout: Duration,
+    ) -> Result<BootRecord, AppError> {
+        let current = self.current().await?;
+        if current == target {
+            return Err(format!("already booting from {}", target).into());
+        }
+        if let Some(record) = self.record().await {
+            if record.state == BootState::Pending {
+                return Err(
+                    format!("switch to {} is still pending verification", record.target).into(),
+                );
+            }
+        }
+
+        let record = BootRecord {
+            previous: current,
+            target,
+            state: BootState::Pending,
+            switched_at: chrono::Utc::now().timestamp(),
+            confirm_timeout_secs: confirm_timeout.as_secs(),
+            booted_at: None,
+        };
+        // 先写记录再切换，切换失败时记录会被清掉
+        self.save(&record).await?;
+        if !self.device.set_boot(target).await? {
+            let _ = tokio::fs::remove_file(&self.record_path).await;
+            return Err(format!("failed to switch boot partition to {}", target).into());
+        }
+        Ok(record)
+    }
+
+    /// 确认新分区健康，取消回滚
+    pub async fn confirm(&self) -> Result<bool, AppError> {
+        let Some(mut record) = self.record().await else {
+            return Ok(false);
+        };
+        if record.state != BootState::Pending {
+            return Ok(false);
+        }
+        if self.current().await? != record.target {
+            return Err(format!("not running from {} yet", record.target).into());
+        }
+        record.state = BootState::Confirmed;
+        self.save(&record).await?;
+        Ok(true)
+    }
+
+    /// 切回原分区并重启
+    pub async fn rollback(&self) -> Result<(), AppError> {
+        let Some(mut record) = self.record().await else {
+            return Err("no boot switch to roll back".into());
+        };
+        if !self.device.set_boot(record.previous).await? {
+            return Err(format!("failed to switch boot partition to {}", record.previous).into());
+        }
+        record.state = BootState::RolledBack;
+        self.save(&record).await?;
+        self.device.reboot().await
+    }
+
+    /// 启动时调用：如果刚切换过分区，在超时前定期检查健康状况，
+    /// 健康则确认，否则回滚到原分区
+    ///
+    /// 超时从新分区第一次开机算起，client 反复崩溃重启也不会延长；
+    /// 即使启动时已经超时，也会先检查一次再决定是否回滚
+    pub async fn verify<F, Fut>(&self, health_check: F) -> Result<(), AppError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let Some(mut record) = self.record().await else {
+            return Ok(());
+        };
+        if record.state != BootState::Pending {
+            return Ok(());
+        }
+        let current = self.current().await?;
+        if current != record.target {
+            // 还没重启到新分区
+            return Ok(());
+        }
+
+        if record.booted_at.is_none() {
+            record.booted_at = Some(self.device.booted_at().await?);
+            self.save(&record).await?;
+        }
+        let remaining = (record.deadline() - chrono::Utc::now().timestamp()).max(0) as u64;
+        let deadline = Instant::now() + Duration::from_secs(remaining);
+        println!(
+            "🩺 [BootManager] verifying {} (rollback to {} in {}s)",
+            record.target, record.previous, remaining
+        );
+        loop {
+            // 先给服务一点启动时间再检查
+            sleep(HEALTH_CHECK_INTERVAL.min(deadline.saturating_duration_since(Instant::now())))
+                .await;
+            if health_check().await {
+                self.confirm().await?;
+                println!("✅ [BootManager] {} confirmed healthy", record.target);
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        eprintln!(
+            "⚠️  [BootManager] {} not confirmed in time, rolling back to {}",
+            record.target, record.previous
+        );
+        self.rollback().await
+    }
+
+    /// 注册分区管理相关的 RPC 命令
+    pub async fn register_commands() {
+        let rpc = RPC::instance();
+        rpc.add_command("get_boot_status", |_| async {
+            Ok(Response::from_data(json!(
+                BootManager::instance().status().await?
+            )))
+        })
+        .await;
+        rpc.add_command("switch_boot", |request| async move {
+            let target = request
+                .params
+                .get("target")
+                .and_then(|v| v.as_str())
+                .ok_or("missing target")?
+                .parse::<BootPartition>()?;
+            let timeout = request
+                .params
+                .get("confirm_timeout_secs")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(600);
+            let record = BootManager::instance()
+                .switch_to(target, Duration::from_secs(timeout))
+                .await?;
+            Ok(Response::from_data(json!(record)))
+        })
+        .await;
+        rpc.add_command("confirm_boot", |_| async {
+            Ok(Response::from_data(json!(
+                BootManager::instance().confirm().await?
+            )))
+        })
+        .await;
+    }
+
+    async fn save(&self, record: &BootRecord) -> Result<(), AppError> {
+        if let Some(dir) = self.record_path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        // 先写临时文件再改名，避免断电留下半个文件
+        let mut tmp = self.record_path.clone().into_os_string();
+        tmp.push(".tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(record)?).await?;
+        tokio::fs::rename(&tmp, &self.record_path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[test]
+    fn deadline_counts_from_the_first_boot() {
+        let mut record = BootRecord {
+            previous: BootPartition::Boot0,
+            target: BootPartition::Boot1,
+            state: BootState::Pending,
+            switched_at: 1_000,
+            confirm_timeout_secs: 600,
+            booted_at: None,
+        };
+        assert_eq!(record.deadline(), 1_600);
+
+        record.booted_at = Some(5_000);
+        assert_eq!(record.deadline(), 5_600);
+
+        // 开机时钟偏慢也不会让期限早于切换时间
+        record.booted_at = Some(10);
+        assert_eq!(record.deadline(), 1_600);
+    }
+
+    /// 只记录切换分区和重启操作、不真正执行的音箱
+    struct FakeBoot {
+        current: Mutex<BootPartition>,
+        booted_at: i64,
+        actions: Mutex<Vec<String>>,
+    }
+
+    impl BootDevice for FakeBoot {
+        fn current(&self) -> BoxFuture<'_, Result<BootPartition, AppError>> {
+            Box::pin(async { Ok(*self.current.lock().unwrap()) })
+        }
+
+        fn set_boot(&self, partition: BootPartition) -> BoxFuture<'_, Result<bool, AppError>> {
+            self.actions
+                .lock()
+                .unwrap()
+                .push(format!("set_boot {}", partition));
+            Box::pin(async { Ok(true) })
+        }
+
+        fn reboot(&self) -> BoxFuture<'_, Result<(), AppError>> {
+            self.actions.lock().unwrap().push("reboot".to_string());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn booted_at(&self) -> BoxFuture<'_, Result<i64, AppError>> {
+            Box::pin(async { Ok(self.booted_at) })
+        }
+    }
+
+    /// `age` 秒前从 boot0 切换到 boot1、超时 60 秒的 BootManager
+    async fn switched(
+        name: &str,
+        running: BootPartition,
+        age: i64,
+    ) -> (BootManager, Arc<FakeBoot>) {
+        let now = chrono::Utc::now().timestamp();
+        let device = Arc::new(FakeBoot {
+            current: Mutex::new(running),
+            booted_at: now - age,
+            actions: Mutex::new(Vec::new()),
+        });
+        let path = std::env::temp_dir().join(format!(
+            "open-xiaoai-boot-{}-{}.json",
+            name,
+            uuid::Uuid::new_v4()
+        ));
+        let manager = BootManager::new(path, device.clone());
+        manager
+            .save(&BootRecord {
+                previous: BootPartition::Boot0,
+                target: BootPartition::Boot1,
+                state: BootState::Pending,
+                switched_at: now - age,
+                confirm_timeout_secs: 60,
+                booted_at: None,
+            })
+            .await
+            .unwrap();
+        (manager, device)
+    }
+
+    fn checks() -> (Arc<AtomicUsize>, impl Fn() -> std::future::Ready<bool>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        (count, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(false)
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn healthy_partition_is_confirmed() {
+        let (manager, device) = switched("confirm", BootPartition::Boot1, 0).await;
+        let count = AtomicUsize::new(0);
+        manager
+            .verify(|| std::future::ready(count.fetch_add(1, Ordering::SeqCst) == 1))
+            .await
+            .unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        let record = manager.record().await.unwrap();
+        assert_eq!(record.state, BootState::Confirmed);
+        assert!(record.booted_at.is_some());
+        assert!(device.actions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unhealthy_partition_rolls_back_at_the_deadline() {
+        let (manager, device) = switched("timeout", BootPartition::Boot1, 0).await;
+        let (count, health_check) = checks();
+        let started = Instant::now();
+        manager.verify(health_check).await.unwrap();
+
+        assert!(started.elapsed() >= Duration::from_secs(60));
+        assert!(count.load(Ordering::SeqCst) >= 4);
+        assert_eq!(manager.record().await.unwrap().state, BootState::RolledBack);
+        assert_eq!(
+            *device.actions.lock().unwrap(),
+            ["set_boot boot0", "reboot"]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_start_still_checks_once_before_rolling_back() {
+        let (manager, device) = switched("late", BootPartition::Boot1, 600).await;
+        let (count, health_check) = checks();
+        manager.verify(health_check).await.unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            *device.actions.lock().unwrap(),
+            ["set_boot boot0", "reboot"]
+        );
+
+        let (manager, device) = switched("late-healthy", BootPartition::Boot1, 600).await;
+        manager.verify(|| std::future::ready(true)).await.unwrap();
+        assert_eq!(manager.record().await.unwrap().state, BootState::Confirmed);
+        assert!(device.actions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nothing_happens_before_the_reboot_into_the_target() {
+        let (manager, device) = switched("pending", BootPartition::Boot0, 0).await;
+        let (count, health_check) = checks();
+        manager.verify(health_check).await.unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        assert_eq!(manager.record().await.unwrap().state, BootState::Pending);
+        assert!(device.actions.lock().unwrap().is_empty());
+        let error = manager.confirm().await.unwrap_err();
+        assert_eq!(error.to_string(), "not running from boot1 yet");
+    }
+}
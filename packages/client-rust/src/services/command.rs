@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::base::AppError;
+use crate::services::connect::{data::Request, rpc::RPC};
+use crate::services::speaker::SpeakerManager;
+use crate::utils::shell;
+
+/// 服务端下发给音箱的指令，格式为 `{"action": "...", ...}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DeviceCommand {
+    /// 播报一段文字
+    Tts {
+        text: String,
+    },
+    PlayUrl {
+        url: String,
+    },
+    Play,
+    Pause,
+    SetVolume {
+        volume: u8,
+    },
+    StepVolume {
+        delta: i32,
+    },
+    Mute,
+    Unmute,
+    /// 唤醒小爱，和说出唤醒词一样
+    WakeUp,
+    /// 让小爱处理一句话，和用户直接说出来一样
+    AskXiaoai {
+        text: String,
+    },
+    MicOn,
+    MicOff,
+    /// 打断小爱当前的回复
+    Abort,
+    /// 执行 shell 脚本，需要在配置中开启 allowShell
+    RunShell {
+        script: String,
+    },
+    /// 重新加载 client 配置
+    ReloadConfig,
+    /// 调用 client 注册的 RPC 方法，例如 get_device_info
+    Rpc {
+        request: Request,
+        /// 过期时间（unix 毫秒），过期后不再执行
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<i64>,
+    },
+}
+
+/// 执行指令时的限制
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandOptions {
+    pub allow_shell: bool,
+}
+
+/// 一条指令的执行结果，随 ack 一起回传给服务端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutcome {
+    pub id: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CommandOutcome {
+    pub fn from_result(id: &str, result: Result<Value, AppError>) -> Self {
+        match result {
+            Ok(data) => Self {
+                id: id.to_string(),
+                ok: true,
+                data: Some(data),
+                error: None,
+            },
+            Err(e) => Self {
+                id: id.to_string(),
+                ok: false,
+                data: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+impl DeviceCommand {
+    /// 解析服务端下发的数据，未知的 action 会返回错误
+    pub fn parse(data: &Value) -> Result<Self, AppError> {
+        Ok(serde_json::from_value(data.clone())?)
+    }
+
+    pub async fn execute(&self, options: &CommandOptions) -> Result<Value, AppError> {
+        let ok = match self {
+            DeviceCommand::Tts { text } => SpeakerManager::play_text(text).await?,
+            DeviceCommand::PlayUrl { url } => SpeakerManager::play_url(url).await?,
+            DeviceCommand::Play => SpeakerManager::play().await?,
+            DeviceCommand::Pause => SpeakerManager::pause().await?,
+            DeviceCommand::SetVolume { volume } => {
+                SpeakerManager::set_volume((*volume).min(100)).await?
+            }
+            DeviceCommand::StepVolume { delta } => {
+                let volume = SpeakerManager::step_volume((*delta).clamp(-100, 100)).await?;
+                return Ok(json!({ "volume": volume }));
+            }
+            DeviceCommand::Mute => SpeakerManager::mute().await?,
+            DeviceCommand::Unmute => SpeakerManager::unmute().await?,
+            DeviceCommand::WakeUp => SpeakerManager::wake_up().await?,
+            DeviceCommand::AskXiaoai { text } => SpeakerManager::ask_xiaoai(text).await?,
+            DeviceCommand::MicOn => SpeakerManager::mic_on().await?,
+            DeviceCommand::MicOff => SpeakerManager::mic_off().await?,
+            DeviceCommand::Abort => {
+                let report = SpeakerManager::abort_xiaoai().await?;
+                return Ok(json!(report));
+            }
+            DeviceCommand::RunShell { script } => {
+                if !options.allow_shell {
+                    return Err("run_shell is disabled on this device".into());
+                }
+                let res = shell::run_shell(script).await?;
+                return Ok(json!(res));
+            }
+            DeviceCommand::ReloadConfig => {
+                // 由 client 注册的 reload_config 命令完成
+                let request = Request::new("reload_config", json!({}));
+                let response = RPC::instance().on_request(request).await?;
+                return Ok(response.data);
+            }
+            DeviceCommand::Rpc {
+                request,
+                expires_at,
+            } => {
+                if expires_at.is_some_and(|at| at <= chrono::Utc::now().timestamp_millis()) {
+                    return Err(format!("rpc {} has expired", request.method).into());
+                }
+                if request.method == "run_shell" && !options.allow_shell {
+                    return Err("run_shell is disabled on this device".into());
+                }
+                let response = RPC::instance().on_request(request.clone()).await?;
+                return Ok(response.data);
+            }
+        };
+        if !ok {
+            return Err(format!("{:?} did not succeed", self).into());
+        }
+        Ok(json!({ "ok": true }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn expired_rpc_is_not_run() {
+        let data = json!({
+            "action": "rpc",
+            "request": Request::new("get_device_info", json!({})),
+            "expires_at": chrono::Utc::now().timestamp_millis() - 1,
+        });
+        let command = DeviceCommand::parse(&data).unwrap();
+        let error = command
+            .execute(&CommandOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "rpc get_device_info has expired");
+    }
+
+    #[test]
+    fn wake_up_ignores_the_old_silent_flag() {
+        let command = DeviceCommand::parse(&json!({ "action": "wake_up", "silent": true }));
+        assert!(matches!(command, Ok(DeviceCommand::WakeUp)));
+    }
+}
@@ -0,0 +1,731 @@
+use reqwest::Url;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+use crate::services::gate::GateConfig;
+use crate::services::router::{Router, RouterConfig};
+use crate::services::speech::SpeechConfig;
+use crate::services::status::StatusConfig;
+use crate::services::update::UpdateConfig;
+
+/// client 的配置文件，支持 JSON 和 TOML
+///
+/// 密钥字段（openai.apiKey、serverProxy.token）可以写成 `env:NAME`、`${NAME}` 或 `file:PATH`，
+/// 加载时替换为环境变量或文件的内容，密钥不必明文写在配置里
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Config {
+    pub mode: Mode,
+    /// direct 模式必填
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub openai: Option<OpenAIConfig>,
+    /// proxy 模式必填
+    #[serde(
+        rename = "serverProxy",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub server_proxy: Option<ServerProxyConfig>,
+    #[serde(default)]
+    pub prompt: PromptConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioConfig>,
+    #[serde(default)]
+    pub router: RouterConfig,
+    #[serde(default)]
+    pub gate: GateConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<UpdateConfig>,
+    #[serde(default)]
+    pub speech: SpeechConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// 直接调用大模型接口
+    Direct,
+    /// 通过服务端代理
+    Proxy,
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Direct => "direct",
+            Mode::Proxy => "proxy",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// OpenAI 兼容的大模型接口
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OpenAIConfig {
+    #[serde(rename = "baseURL")]
+    pub base_url: String,
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+    pub model: String,
+    /// 请求超时秒数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(rename = "maxTokens", default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// 允许大模型调用音箱工具（音量、静音等）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<bool>,
+    /// 遇到 429 或 5xx 时对同一接口重试的次数，默认 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// 主接口不可用时按顺序尝试的备用接口
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<ProviderConfig>,
+}
+
+impl OpenAIConfig {
+    /// 按尝试顺序排列的接口，主接口在最前
+    pub fn providers(&self) -> Vec<ProviderConfig> {
+        let primary = ProviderConfig {
+            name: None,
+            base_url: self.base_url.clone(),
+            api_key: self.api_key.clone(),
+            model: self.model.clone(),
+            timeout: self.timeout,
+        };
+        std::iter::once(primary)
+            .chain(self.fallbacks.iter().map(|provider| ProviderConfig {
+                timeout: provider.timeout.or(self.timeout),
+                ..provider.clone()
+            }))
+            .collect()
+    }
+}
+
+/// 一个 OpenAI 兼容的接口
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProviderConfig {
+    /// 日志和状态中显示的名称，默认为 baseURL 的主机名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "baseURL")]
+    pub base_url: String,
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+    pub model: String,
+    /// 请求超时秒数，默认与 openai.timeout 相同
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+impl ProviderConfig {
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        Url::parse(&self.base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.base_url.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ServerProxyConfig {
+    #[serde(rename = "baseURL")]
+    pub base_url: String,
+    /// 请求超时秒数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// 允许服务端下发 run_shell 指令
+    #[serde(rename = "allowShell", default)]
+    pub allow_shell: bool,
+    /// 注册设备时使用的令牌，对应服务端的 auth.registrationToken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// 使用注册时下发的设备密钥验证请求的方式
+    #[serde(default)]
+    pub auth: AuthScheme,
+    #[serde(default)]
+    pub offline: OfflineConfig,
+}
+
+/// 服务端不可用时的处理
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OfflineConfig {
+    /// 保存未发送事件的文件
+    #[serde(rename = "outboxPath", default = "OfflineConfig::default_outbox_path")]
+    pub outbox_path: String,
+    /// 最多保存的事件数，超过时丢弃最早的
+    #[serde(rename = "outboxSize", default = "OfflineConfig::default_outbox_size")]
+    pub outbox_size: usize,
+    /// 连续失败多少次后暂停请求
+    #[serde(
+        rename = "failureThreshold",
+        default = "OfflineConfig::default_failure_threshold"
+    )]
+    pub failure_threshold: u32,
+    /// 暂停请求的秒数，之后再试探服务端是否恢复
+    #[serde(default = "OfflineConfig::default_cooldown")]
+    pub cooldown: u64,
+    /// 连不上服务端时播报的提示，留空则不播报
+    #[serde(
+        rename = "fallbackMessage",
+        default = "OfflineConfig::default_fallback_message"
+    )]
+    pub fallback_message: String,
+}
+
+impl OfflineConfig {
+    fn default_outbox_path() -> String {
+        "/data/open-xiaoai/outbox.jsonl".to_string()
+    }
+
+    fn default_outbox_size() -> usize {
+        100
+    }
+
+    fn default_failure_threshold() -> u32 {
+        3
+    }
+
+    fn default_cooldown() -> u64 {
+        30
+    }
+
+    fn default_fallback_message() -> String {
+        "我现在连不上服务器，恢复后再回答你".to_string()
+    }
+}
+
+impl Default for OfflineConfig {
+    fn default() -> Self {
+        Self {
+            outbox_path: OfflineConfig::default_outbox_path(),
+            outbox_size: OfflineConfig::default_outbox_size(),
+            failure_threshold: OfflineConfig::default_failure_threshold(),
+            cooldown: OfflineConfig::default_cooldown(),
+            fallback_message: OfflineConfig::default_fallback_message(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// 用设备密钥对每个请求做 HMAC-SHA256 签名
+    #[default]
+    Hmac,
+    /// 直接把设备密钥作为 Bearer 令牌
+    Bearer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PromptConfig {
+    pub system: String,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            system: "你是一个智能助手，请根据用户的问题给出回答。".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AudioConfig {
+    #[serde(rename = "sampleRate")]
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub format: String,
+}
+
+/// 配置校验失败，包含所有发现的问题
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+impl ConfigError {
+    fn new(error: impl fmt::Display) -> Self {
+        Self {
+            errors: vec![error.to_string()],
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.errors.as_slice() {
+            [error] => write!(f, "invalid config: {}", error),
+            errors => {
+                write!(f, "invalid config:")?;
+                for error in errors {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// 反序列化之后的语义校验
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+
+    /// 当前配置用不到的顶层字段，加载时直接忽略，其中的 `env:` 引用也不必设置
+    fn unused_sections(_value: &Value) -> Vec<&'static str>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// 可以引用环境变量或文件的密钥字段，路径中的 `*` 匹配数组的任意一项
+    fn secret_fields() -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+}
+
+/// 收集校验错误，错误信息形如 `openai.baseURL must be an http(s) URL`
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<String>,
+}
+
+impl Validator {
+    pub fn error(&mut self, path: &str, message: impl fmt::Display) {
+        self.errors.push(format!("{} {}", path, message));
+    }
+
+    pub fn not_empty(&mut self, path: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.error(path, "must not be empty");
+        }
+    }
+
+    pub fn url(&mut self, path: &str, value: &str) {
+        let valid = Url::parse(value)
+            .map(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+            .unwrap_or(false);
+        if !valid {
+            self.error(path, "must be an http(s) URL");
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ConfigError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError {
+                errors: self.errors,
+            })
+        }
+    }
+}
+
+impl Validate for OpenAIConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.url("openai.baseURL", &self.base_url);
+        v.not_empty("openai.apiKey", &self.api_key);
+        v.not_empty("openai.model", &self.model);
+        if self.timeout == Some(0) {
+            v.error("openai.timeout", "must be greater than 0");
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                v.error("openai.temperature", "must be between 0 and 2");
+            }
+        }
+        for (i, provider) in self.fallbacks.iter().enumerate() {
+            let path = |field: &str| format!("openai.fallbacks[{}].{}", i, field);
+            v.url(&path("baseURL"), &provider.base_url);
+            v.not_empty(&path("apiKey"), &provider.api_key);
+            v.not_empty(&path("model"), &provider.model);
+            if provider.timeout == Some(0) {
+                v.error(&path("timeout"), "must be greater than 0");
+            }
+        }
+    }
+}
+
+impl Validate for Config {
+    fn unused_sections(value: &Value) -> Vec<&'static str> {
+        // openai 只在 direct 模式使用；serverProxy 在 direct 模式下还是更新清单的默认来源
+        match value.get("mode").and_then(Value::as_str) {
+            Some("proxy") => vec!["openai"],
+            _ => Vec::new(),
+        }
+    }
+
+    fn secret_fields() -> &'static [&'static str] {
+        &[
+            "openai.apiKey",
+            "openai.fallbacks.*.apiKey",
+            "serverProxy.token",
+        ]
+    }
+
+    fn validate(&self, v: &mut Validator) {
+        match self.mode {
+            Mode::Direct if self.openai.is_none() => {
+                v.error("openai", "is required in direct mode");
+            }
+            Mode::Proxy if self.server_proxy.is_none() => {
+                v.error("serverProxy", "is required in proxy mode");
+            }
+            _ => {}
+        }
+        if let Some(openai) = &self.openai {
+            openai.validate(v);
+        }
+        if let Some(proxy) = &self.server_proxy {
+            v.url("serverProxy.baseURL", &proxy.base_url);
+            if proxy.timeout == Some(0) {
+                v.error("serverProxy.timeout", "must be greater than 0");
+            }
+            v.not_empty("serverProxy.offline.outboxPath", &proxy.offline.outbox_path);
+            if proxy.offline.outbox_size == 0 {
+                v.error("serverProxy.offline.outboxSize", "must be greater than 0");
+            }
+            if proxy.offline.failure_threshold == 0 {
+                v.error(
+                    "serverProxy.offline.failureThreshold",
+                    "must be greater than 0",
+                );
+            }
+        }
+        v.not_empty("prompt.system", &self.prompt.system);
+        if let Err(e) = Router::new(self.router.clone()) {
+            v.error("router", e);
+        }
+        if let Some(status) = &self.status {
+            if status.addr().is_err() {
+                v.error("status.listen", "must be an address like 127.0.0.1:4398");
+            }
+        }
+        if let Some(update) = &self.update {
+            if let Some(url) = &update.url {
+                v.url("update.url", url);
+            }
+            let key = hex::decode(update.public_key.trim()).unwrap_or_default();
+            if key.len() != 32 {
+                v.error(
+                    "update.publicKey",
+                    "must be a hex encoded ed25519 public key",
+                );
+            }
+        }
+    }
+}
+
+impl Config {
+    /// 配置文件的 JSON Schema
+    pub fn schema() -> Value {
+        serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default()
+    }
+
+    /// 构建 LLM 服务用到的配置是否没变，没变时重载配置可以沿用原来的服务和它的状态
+    pub fn same_llm_config(&self, next: &Config) -> bool {
+        self.mode == next.mode
+            && match next.mode {
+                Mode::Direct => {
+                    same_section(&self.openai, &next.openai)
+                        && self.prompt.system == next.prompt.system
+                }
+                Mode::Proxy => same_section(&self.server_proxy, &next.server_proxy),
+            }
+    }
+}
+
+/// 按序列化后的内容比较两份配置
+pub fn same_section<T: Serialize>(current: &T, next: &T) -> bool {
+    serde_json::to_value(current).ok() == serde_json::to_value(next).ok()
+}
+
+/// 配置文件格式，字段和 JSON 完全一样
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// 按扩展名判断，.toml 之外都当作 JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Format::Toml,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// 读取并校验配置文件
+pub fn load<T: DeserializeOwned + Validate>(path: impl AsRef<Path>) -> Result<T, ConfigError> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| ConfigError::new(format!("failed to read {}: {}", path.display(), e)))?;
+    parse(&content, Format::from_path(path))
+}
+
+/// 解析并校验配置内容
+pub fn parse<T: DeserializeOwned + Validate>(
+    content: &str,
+    format: Format,
+) -> Result<T, ConfigError> {
+    let mut value: Value = match format {
+        Format::Json => serde_json::from_str(content)
+            .map_err(|e| ConfigError::new(format!("not valid JSON: {}", e)))?,
+        Format::Toml => toml::from_str(content)
+            .map_err(|e| ConfigError::new(format!("not valid TOML: {}", e)))?,
+    };
+
+    for section in T::unused_sections(&value) {
+        if let Some(map) = value.as_object_mut() {
+            map.remove(section);
+        }
+    }
+
+    let mut errors = Vec::new();
+    resolve_secrets("", &mut value, T::secret_fields(), &mut errors);
+    if !errors.is_empty() {
+        return Err(ConfigError { errors });
+    }
+
+    let config: T = serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        let message = e.inner().to_string();
+        // 缺少字段时报告字段的完整路径：openai.apiKey is required
+        let missing = message
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.strip_suffix('`'));
+        match (path.as_str(), missing) {
+            (".", Some(field)) => ConfigError::new(format!("{} is required", field)),
+            (_, Some(field)) => ConfigError::new(format!("{}.{} is required", path, field)),
+            (".", None) => ConfigError::new(message),
+            _ => ConfigError::new(format!("{}: {}", path, message)),
+        }
+    })?;
+
+    let mut validator = Validator::default();
+    config.validate(&mut validator);
+    validator.finish()?;
+    Ok(config)
+}
+
+/// 替换密钥字段中 `env:NAME`、`${NAME}` 和 `file:PATH` 形式的引用，其他字符串保持原样
+fn resolve_secrets(path: &str, value: &mut Value, fields: &[&str], errors: &mut Vec<String>) {
+    let child = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                resolve_secrets(&child(key), value, fields, errors);
+            }
+        }
+        Value::Array(items) => {
+            for (index, value) in items.iter_mut().enumerate() {
+                resolve_secrets(&child(&index.to_string()), value, fields, errors);
+            }
+        }
+        Value::String(s) if is_secret_field(path, fields) => match resolve_secret(s) {
+            Ok(Some(resolved)) => *s = resolved,
+            Ok(None) => {}
+            Err(e) => errors.push(format!("{} {}", path, e)),
+        },
+        _ => {}
+    }
+}
+
+fn is_secret_field(path: &str, fields: &[&str]) -> bool {
+    fields.iter().any(|field| {
+        let (mut path, mut field) = (path.split('.'), field.split('.'));
+        loop {
+            match (path.next(), field.next()) {
+                (None, None) => return true,
+                (Some(part), Some(pattern)) if pattern == "*" || pattern == part => {}
+                _ => return false,
+            }
+        }
+    })
+}
+
+/// 不是引用时返回 None
+pub fn resolve_secret(value: &str) -> Result<Option<String>, String> {
+    let env_name = value.strip_prefix("env:").or_else(|| {
+        value
+            .strip_prefix("${")
+            .and_then(|name| name.strip_suffix('}'))
+            .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+    });
+    if let Some(name) = env_name {
+        return std::env::var(name.trim())
+            .map(Some)
+            .map_err(|_| format!("refers to environment variable {} which is not set", name));
+    }
+    if let Some(file) = value.strip_prefix("file:") {
+        return std::fs::read_to_string(file.trim())
+            .map(|content| Some(content.trim().to_string()))
+            .map_err(|e| format!("refers to {} which cannot be read: {}", file, e));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::router::RouteMatcher;
+
+    #[test]
+    fn proxy_mode_ignores_the_openai_section() {
+        let content = r#"{
+            "mode": "proxy",
+            "openai": { "baseURL": "", "apiKey": "env:OPEN_XIAOAI_UNSET_KEY", "model": "" },
+            "serverProxy": { "baseURL": "http://127.0.0.1:4399" }
+        }"#;
+        let config: Config = parse(content, Format::Json).unwrap();
+        assert!(config.openai.is_none());
+
+        let content = content.replace("\"proxy\"", "\"direct\"");
+        let error = parse::<Config>(&content, Format::Json).unwrap_err();
+        assert!(
+            error.to_string().contains("OPEN_XIAOAI_UNSET_KEY"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn reload_compares_only_the_sections_a_service_is_built_from() {
+        let config = |model: &str, max_chars: u32| -> Config {
+            let content = serde_json::json!({
+                "mode": "direct",
+                "openai": { "baseURL": "http://127.0.0.1:9", "apiKey": "key", "model": model },
+                "speech": { "maxChars": max_chars }
+            });
+            parse(&content.to_string(), Format::Json).unwrap()
+        };
+
+        let first = config("first", 100);
+        let second = config("first", 200);
+        assert!(first.same_llm_config(&second));
+        assert!(!same_section(&first.speech, &second.speech));
+
+        let third = config("second", 200);
+        assert!(!second.same_llm_config(&third));
+        assert!(same_section(&second.speech, &third.speech));
+
+        let mut proxy = third.clone();
+        proxy.mode = Mode::Proxy;
+        assert!(!third.same_llm_config(&proxy));
+    }
+
+    fn direct(openai: &str) -> String {
+        format!(r#"{{ "mode": "direct", "openai": {} }}"#, openai)
+    }
+
+    #[test]
+    fn secrets_are_read_from_env_and_files() {
+        std::env::set_var("OPEN_XIAOAI_TEST_KEY", "sk-from-env");
+        let file = std::env::temp_dir().join("open-xiaoai-test-token");
+        std::fs::write(&file, "token-from-file\n").unwrap();
+
+        let content = format!(
+            r#"{{
+                "mode": "direct",
+                "openai": {{
+                    "baseURL": "https://api.example.com/v1",
+                    "apiKey": "env:OPEN_XIAOAI_TEST_KEY",
+                    "model": "gpt",
+                    "fallbacks": [{{ "baseURL": "https://backup.example.com/v1", "apiKey": "${{OPEN_XIAOAI_TEST_KEY}}", "model": "gpt" }}]
+                }},
+                "serverProxy": {{ "baseURL": "http://127.0.0.1:4399", "token": "file:{}" }}
+            }}"#,
+            file.display()
+        );
+        let config: Config = parse(&content, Format::Json).unwrap();
+        let openai = config.openai.unwrap();
+        assert_eq!(openai.api_key, "sk-from-env");
+        assert_eq!(openai.fallbacks[0].api_key, "sk-from-env");
+        assert_eq!(
+            config.server_proxy.unwrap().token.as_deref(),
+            Some("token-from-file")
+        );
+    }
+
+    #[test]
+    fn only_secret_fields_are_resolved() {
+        let content = r#"{
+            "mode": "direct",
+            "openai": { "baseURL": "https://api.example.com/v1", "apiKey": "sk", "model": "gpt" },
+            "prompt": { "system": "file:/etc/passwd" },
+            "router": {
+                "rules": [{ "match": { "keyword": ["env:", "${HOME}"] }, "action": { "type": "llm" } }]
+            }
+        }"#;
+        let config: Config = parse(content, Format::Json).unwrap();
+        assert_eq!(config.prompt.system, "file:/etc/passwd");
+        let RouteMatcher::Keyword(keywords) = &config.router.rules[0].matcher else {
+            panic!("expected a keyword matcher");
+        };
+        assert_eq!(keywords, &["env:", "${HOME}"]);
+    }
+
+    #[test]
+    fn missing_variable_is_reported_with_its_path() {
+        let content = direct(
+            r#"{ "baseURL": "https://api.example.com/v1", "apiKey": "env:OPEN_XIAOAI_UNSET_KEY", "model": "gpt" }"#,
+        );
+        let error = parse::<Config>(&content, Format::Json).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid config: openai.apiKey refers to environment variable OPEN_XIAOAI_UNSET_KEY which is not set"
+        );
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let content = direct(r#"{ "baseURL": "ftp://example.com", "apiKey": "sk", "model": " " }"#);
+        let error = parse::<Config>(&content, Format::Json).unwrap_err();
+        assert_eq!(
+            error.errors,
+            [
+                "openai.baseURL must be an http(s) URL",
+                "openai.model must not be empty"
+            ]
+        );
+
+        let content = direct(r#"{ "baseURL": "https://api.example.com/v1", "apiKey": "sk" }"#);
+        let error = parse::<Config>(&content, Format::Json).unwrap_err();
+        assert_eq!(error.errors, ["openai.model is required"]);
+
+        let error = parse::<Config>("{}", Format::Json).unwrap_err();
+        assert_eq!(error.errors, ["mode is required"]);
+
+        let error = parse::<Config>(r#"{ "mode": "direct" }"#, Format::Json).unwrap_err();
+        assert_eq!(error.errors, ["openai is required in direct mode"]);
+    }
+}
@@ -10,6 +10,7 @@ use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
 
 use super::rpc::RPC;
 use crate::base::AppError;
+use crate::utils::event::{EventBus, Subscription};
 use crate::utils::task::TaskManager;
 
 use super::data::{AppMessage, Event, Request, Response, Stream};
@@ -30,10 +31,15 @@ pub enum WsWriter {
     Client(SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>),
 }
 
+/// 连接建立后转发给对端的 EventBus 主题，instruction.log 的原始消息不转发
+pub const FORWARDED_TOPICS: [&str; 4] =
+    ["kws.*", "instruction.recognized", "playing.*", "volume.*"];
+
 pub struct MessageManager {
     semaphore: Arc<Semaphore>,
     reader: Arc<Mutex<Option<WsReader>>>,
     writer: Arc<Mutex<Option<WsWriter>>>,
+    forwarding: Arc<Mutex<Vec<Subscription>>>,
 }
 
 static INSTANCE: LazyLock<MessageManager> = LazyLock::new(MessageManager::new);
@@ -44,6 +50,7 @@ impl MessageManager {
             reader: Arc::new(Mutex::new(None)),
             writer: Arc::new(Mutex::new(None)),
             semaphore: Arc::new(Semaphore::new(32)),
+            forwarding: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -72,9 +79,11 @@ impl MessageManager {
                     .await
             })
             .await;
+        self.forward_events().await;
     }
 
     pub async fn dispose(&self) {
+        self.forwarding.lock().await.clear();
         *self.reader.lock().await = None;
         *self.writer.lock().await = None;
         RPC::instance().dispose().await;
@@ -96,8 +105,24 @@ impl MessageManager {
         Ok(())
     }
 
+    /// 把本地 EventBus 上的事件转发给对端
+    async fn forward_events(&self) {
+        let mut forwarding = self.forwarding.lock().await;
+        forwarding.clear();
+        for topic in FORWARDED_TOPICS {
+            let subscription = EventBus::instance()
+                .subscribe(topic, |event| async move {
+                    MessageManager::instance()
+                        .send_event(&event.name, Some(event.data))
+                        .await
+                })
+                .await;
+            forwarding.push(subscription);
+        }
+    }
+
     pub async fn send_event(&self, event: &str, data: Option<Value>) -> Result<(), AppError> {
-        let event: Event = Event::new(event, data);
+        let event: Event = Event::new(event, data.unwrap_or_default());
         let data = serde_json::to_string(&AppMessage::Event(event)).unwrap();
         MessageManager::instance()
             .send(Message::Text(data.into()))
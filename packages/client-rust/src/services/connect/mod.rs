@@ -1 +1,4 @@
 pub mod data;
+pub mod handler;
+pub mod message;
+pub mod rpc;
@@ -75,7 +75,7 @@ impl RPC {
 
     /// local 收到 remote 调用
     pub async fn on_request(&self, request: Request) -> Result<Response, AppError> {
-        let handler = self.get_request_handler(&request.command).await;
+        let handler = self.get_request_handler(&request.method).await;
         match handler {
             Some(handler) => handler(request).await,
             None => Err("command not found".into()),
@@ -111,8 +111,8 @@ impl RPC {
 
         let request = Request {
             id: uid.clone(),
-            command: command.to_string(),
-            payload,
+            method: command.to_string(),
+            params: payload.unwrap_or_default(),
         };
 
         send_request(request).await?;
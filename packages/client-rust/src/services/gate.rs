@@ -0,0 +1,309 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use crate::base::AppError;
+use crate::services::connect::data::Event;
+use crate::utils::event::EventBus;
+
+/// 唤醒门控：决定一条识别结果是否应该由我们处理
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GateConfig {
+    #[serde(default)]
+    pub mode: GateMode,
+    /// 自定义唤醒词命中后，窗口保持打开的秒数
+    #[serde(rename = "wakeWindow", default = "GateConfig::default_wake_window")]
+    pub wake_window: u64,
+    /// 助手说完之后，允许直接追问的秒数，0 表示不开启连续对话
+    #[serde(rename = "followUpWindow", default)]
+    pub follow_up_window: u64,
+    /// 只有这些唤醒词才会打开窗口，为空时任意唤醒词都可以
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+impl GateConfig {
+    fn default_wake_window() -> u64 {
+        10
+    }
+}
+
+impl Default for GateConfig {
+    fn default() -> Self {
+        Self {
+            mode: GateMode::default(),
+            wake_window: GateConfig::default_wake_window(),
+            follow_up_window: 0,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GateMode {
+    /// 小爱原生唤醒即可，所有识别结果都会被处理
+    #[default]
+    Native,
+    /// 只处理自定义唤醒词或连续对话窗口内的识别结果
+    CustomWakeWord,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GateReason {
+    Keyword,
+    FollowUp,
+}
+
+/// 门控的当前状态，用于事件和状态接口
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GateStatus {
+    pub mode: GateMode,
+    pub open: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<GateReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+    /// 窗口剩余的毫秒数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_ms: Option<u64>,
+}
+
+#[derive(Default)]
+struct GateWindow {
+    until: Option<Instant>,
+    reason: Option<GateReason>,
+    keyword: Option<String>,
+    generation: u64,
+}
+
+pub struct WakeGate {
+    config: GateConfig,
+    window: Arc<Mutex<GateWindow>>,
+}
+
+impl WakeGate {
+    pub fn new(config: GateConfig) -> Self {
+        Self {
+            config,
+            window: Arc::new(Mutex::new(GateWindow::default())),
+        }
+    }
+
+    pub fn config(&self) -> &GateConfig {
+        &self.config
+    }
+
+    /// 自定义唤醒词命中，返回窗口是否被打开
+    pub async fn on_keyword(&self, keyword: &str) -> bool {
+        if !self.config.keywords.is_empty() && !self.config.keywords.iter().any(|k| k == keyword) {
+            return false;
+        }
+        self.open(
+            GateReason::Keyword,
+            Some(keyword.to_string()),
+            self.config.wake_window,
+        )
+        .await
+    }
+
+    /// 助手回复结束，返回是否进入连续对话窗口
+    pub async fn on_reply_finished(&self) -> bool {
+        self.open(GateReason::FollowUp, None, self.config.follow_up_window)
+            .await
+    }
+
+    /// 助手回复结束后进入连续对话窗口，并通过 wake 让小爱重新开始收音
+    ///
+    /// 返回是否进入了窗口，没有开启连续对话时不会调用 wake
+    pub async fn follow_up<F, Fut>(&self, wake: F) -> Result<bool, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<bool, AppError>>,
+    {
+        if !self.on_reply_finished().await {
+            return Ok(false);
+        }
+        wake().await?;
+        Ok(true)
+    }
+
+    /// 当前的识别结果是否应该被处理
+    pub async fn allow(&self) -> bool {
+        match self.config.mode {
+            GateMode::Native => true,
+            GateMode::CustomWakeWord => self.is_open().await,
+        }
+    }
+
+    pub async fn is_open(&self) -> bool {
+        let window = self.window.lock().await;
+        window.until.is_some_and(|until| until > Instant::now())
+    }
+
+    pub async fn close(&self) {
+        let closed = {
+            let mut window = self.window.lock().await;
+            let was_open = window.until.is_some_and(|until| until > Instant::now());
+            window.until = None;
+            window.reason = None;
+            window.keyword = None;
+            window.generation += 1;
+            was_open
+        };
+        if closed {
+            self.publish("gate.closed").await;
+        }
+    }
+
+    pub async fn status(&self) -> GateStatus {
+        let window = self.window.lock().await;
+        let now = Instant::now();
+        let remaining = window
+            .until
+            .filter(|until| *until > now)
+            .map(|until| until.duration_since(now));
+        GateStatus {
+            mode: self.config.mode,
+            open: remaining.is_some(),
+            reason: remaining.and(window.reason),
+            keyword: remaining.and(window.keyword.clone()),
+            remaining_ms: remaining.map(|d| d.as_millis() as u64),
+        }
+    }
+
+    async fn open(&self, reason: GateReason, keyword: Option<String>, seconds: u64) -> bool {
+        if seconds == 0 {
+            return false;
+        }
+        let duration = Duration::from_secs(seconds);
+        let generation = {
+            let mut window = self.window.lock().await;
+            window.until = Some(Instant::now() + duration);
+            window.reason = Some(reason);
+            window.keyword = keyword;
+            window.generation += 1;
+            window.generation
+        };
+        self.publish("gate.opened").await;
+
+        // 窗口到期后发出关闭事件，期间重新打开过则由新的窗口负责
+        let window = Arc::clone(&self.window);
+        let mode = self.config.mode;
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            {
+                let mut window = window.lock().await;
+                if window.generation != generation {
+                    return;
+                }
+                window.until = None;
+                window.reason = None;
+                window.keyword = None;
+            }
+            let status = GateStatus {
+                mode,
+                open: false,
+                reason: None,
+                keyword: None,
+                remaining_ms: None,
+            };
+            EventBus::instance()
+                .publish(Event::new("gate.closed", json!(status)))
+                .await;
+        });
+        true
+    }
+
+    async fn publish(&self, name: &str) {
+        let status = self.status().await;
+        EventBus::instance()
+            .publish(Event::new(name, json!(status)))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn gate(mode: GateMode, keywords: &[&str], follow_up_window: u64) -> WakeGate {
+        WakeGate::new(GateConfig {
+            mode,
+            wake_window: 10,
+            follow_up_window,
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keyword_opens_the_window_until_it_expires() {
+        let gate = gate(GateMode::CustomWakeWord, &["小智"], 0);
+        assert!(!gate.allow().await);
+
+        assert!(!gate.on_keyword("小爱同学").await);
+        assert!(!gate.allow().await);
+
+        assert!(gate.on_keyword("小智").await);
+        assert!(gate.allow().await);
+        assert_eq!(gate.status().await.reason, Some(GateReason::Keyword));
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+        assert!(!gate.allow().await);
+        assert!(!gate.status().await.open);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follow_up_window_is_optional() {
+        let single_turn = gate(GateMode::CustomWakeWord, &[], 0);
+        assert!(!single_turn.on_reply_finished().await);
+        assert!(!single_turn.allow().await);
+
+        let conversation = gate(GateMode::CustomWakeWord, &[], 5);
+        assert!(conversation.on_reply_finished().await);
+        assert!(conversation.allow().await);
+        conversation.close().await;
+        assert!(!conversation.allow().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follow_up_wakes_xiaoai_only_when_the_window_opens() {
+        let woken = AtomicUsize::new(0);
+        let wake = || async {
+            woken.fetch_add(1, Ordering::SeqCst);
+            Ok(true)
+        };
+
+        let single_turn = gate(GateMode::CustomWakeWord, &[], 0);
+        assert!(!single_turn.follow_up(wake).await.unwrap());
+        assert_eq!(woken.load(Ordering::SeqCst), 0);
+
+        let conversation = gate(GateMode::CustomWakeWord, &[], 5);
+        assert!(conversation.follow_up(wake).await.unwrap());
+        assert_eq!(woken.load(Ordering::SeqCst), 1);
+        assert!(conversation.allow().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reopening_outlives_the_earlier_window() {
+        let gate = gate(GateMode::CustomWakeWord, &[], 0);
+        gate.on_keyword("小智").await;
+        tokio::time::advance(Duration::from_secs(8)).await;
+        gate.on_keyword("小智").await;
+
+        // 第一个窗口到期不能关掉第二个窗口
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert!(gate.allow().await);
+    }
+
+    #[tokio::test]
+    async fn native_mode_allows_everything() {
+        assert!(gate(GateMode::Native, &["小智"], 0).allow().await);
+    }
+}
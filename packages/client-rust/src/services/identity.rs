@@ -0,0 +1,108 @@
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::services::speaker::SpeakerManager;
+
+/// 默认保存身份信息的目录，刷机前不会被清除
+pub const IDENTITY_DIR: &str = "/data/open-xiaoai";
+
+/// 设备身份：client id 和服务端下发的设备密钥
+///
+/// client id 优先读取已保存的值，其次由序列号和型号推导，都没有时随机生成，
+/// 因此重启和清除 /data 后音箱在服务端仍是同一个设备
+pub struct Identity {
+    dir: PathBuf,
+}
+
+impl Default for Identity {
+    fn default() -> Self {
+        Self::new(IDENTITY_DIR)
+    }
+}
+
+impl Identity {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn client_id_path(&self) -> PathBuf {
+        self.dir.join("client-id")
+    }
+
+    fn secret_path(&self) -> PathBuf {
+        self.dir.join("client-secret")
+    }
+
+    /// 读取或生成 client id，生成后会保存下来
+    pub async fn client_id(&self) -> String {
+        if let Some(id) = read_trimmed(&self.client_id_path()) {
+            return id;
+        }
+        let model = SpeakerManager::get_device_model().await.unwrap_or_default();
+        let serial = SpeakerManager::get_device_sn().await.unwrap_or_default();
+        let id = derive_client_id(&model, &serial).unwrap_or_else(|| Uuid::new_v4().to_string());
+        if let Err(e) = write_private(&self.client_id_path(), &id) {
+            eprintln!(
+                "⚠️  [Identity] failed to save client id to {}: {}",
+                self.client_id_path().display(),
+                e
+            );
+        }
+        id
+    }
+
+    /// 上次注册时服务端下发的设备密钥
+    pub fn secret(&self) -> Option<String> {
+        read_trimmed(&self.secret_path())
+    }
+
+    pub fn save_secret(&self, secret: &str) -> std::io::Result<()> {
+        write_private(&self.secret_path(), secret)
+    }
+
+    pub fn forget_secret(&self) {
+        let _ = std::fs::remove_file(self.secret_path());
+    }
+}
+
+/// 由型号和序列号推导 client id，例如 `lx06-3f5a...`，不直接暴露序列号
+pub fn derive_client_id(model: &str, serial: &str) -> Option<String> {
+    let (model, serial) = (model.trim(), serial.trim());
+    if serial.is_empty() {
+        return None;
+    }
+    let digest = hex::encode(Sha256::digest(format!("{}:{}", model, serial)));
+    let prefix: String = model
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    Some(match prefix.is_empty() {
+        true => digest[..16].to_string(),
+        false => format!("{}-{}", prefix, &digest[..16]),
+    })
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let content = content.trim();
+    (!content.is_empty()).then(|| content.to_string())
+}
+
+/// 先写临时文件再改名，只有当前用户可读
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, path)
+}
@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Instant;
+
+use crate::base::AppError;
+use crate::utils::shell::{run_shell, CommandResult};
+
+/// 打断小爱原生回复的策略，按代价从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptStrategy {
+    /// 通过 ubus 停止 mediaplayer 当前的 TTS / 播放
+    StopPlayer,
+    /// 暂停播放
+    PausePlayback,
+    /// 本轮对话期间静音输出，回复前需要 restore
+    MuteOutput,
+    /// 重启 mico_aivs_lab，最后手段
+    RestartService,
+}
+
+impl InterruptStrategy {
+    /// 默认的打断顺序，重启服务只作为兜底
+    pub const DEFAULT_CHAIN: [InterruptStrategy; 4] = [
+        InterruptStrategy::StopPlayer,
+        InterruptStrategy::PausePlayback,
+        InterruptStrategy::MuteOutput,
+        InterruptStrategy::RestartService,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InterruptStrategy::StopPlayer => "stop_player",
+            InterruptStrategy::PausePlayback => "pause_playback",
+            InterruptStrategy::MuteOutput => "mute_output",
+            InterruptStrategy::RestartService => "restart_service",
+        }
+    }
+
+    pub fn script(&self) -> &'static str {
+        match self {
+            InterruptStrategy::StopPlayer => {
+                r#"ubus -t1 -S call mediaplayer player_play_operation '{"action":"stop"}' 2>&1"#
+            }
+            InterruptStrategy::PausePlayback => "mphelper pause",
+            InterruptStrategy::MuteOutput => {
+                r#"amixer get Master | grep -q '\[off\]' && echo was_muted; amixer -q sset Master mute 2>&1"#
+            }
+            InterruptStrategy::RestartService => {
+                "/etc/init.d/mico_aivs_lab restart >/dev/null 2>&1"
+            }
+        }
+    }
+
+    /// 撤销打断带来的副作用，只有静音需要恢复
+    ///
+    /// 打断前已经是静音时不需要恢复，见 [`InterruptAttempt::was_muted`]
+    pub fn restore_script(&self) -> Option<&'static str> {
+        match self {
+            InterruptStrategy::MuteOutput => Some("amixer -q sset Master unmute 2>&1"),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self, res: &CommandResult) -> bool {
+        match self {
+            InterruptStrategy::StopPlayer => res.stdout.contains("\"code\":0"),
+            InterruptStrategy::PausePlayback => res.stdout.contains("\"code\": 0"),
+            InterruptStrategy::MuteOutput | InterruptStrategy::RestartService => res.exit_code == 0,
+        }
+    }
+
+    /// 打断前输出是否已经静音
+    fn was_muted(&self, res: &CommandResult) -> bool {
+        *self == InterruptStrategy::MuteOutput && res.stdout.contains("was_muted")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptAttempt {
+    pub strategy: InterruptStrategy,
+    pub success: bool,
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 打断前输出已经是静音，restore 时保持静音
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub was_muted: bool,
+}
+
+/// 一次打断的完整过程，记录每个策略的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InterruptReport {
+    pub attempts: Vec<InterruptAttempt>,
+}
+
+impl InterruptReport {
+    /// 最终生效的策略
+    pub fn strategy(&self) -> Option<InterruptStrategy> {
+        self.attempts
+            .iter()
+            .find(|attempt| attempt.success)
+            .map(|attempt| attempt.strategy)
+    }
+
+    pub fn success(&self) -> bool {
+        self.strategy().is_some()
+    }
+}
+
+impl std::fmt::Display for InterruptReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let attempts = self
+            .attempts
+            .iter()
+            .map(|attempt| {
+                let status = if attempt.success { "ok" } else { "failed" };
+                format!(
+                    "{}={} ({}ms)",
+                    attempt.strategy.name(),
+                    status,
+                    attempt.elapsed_ms
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{}", attempts)
+    }
+}
+
+pub struct Interrupter;
+
+impl Interrupter {
+    /// 在本机按默认顺序打断小爱
+    pub async fn interrupt() -> InterruptReport {
+        Interrupter::interrupt_with(&InterruptStrategy::DEFAULT_CHAIN, |script| async move {
+            run_shell(&script).await
+        })
+        .await
+    }
+
+    /// 依次尝试各个策略，直到有一个成功为止
+    pub async fn interrupt_with<F, Fut>(strategies: &[InterruptStrategy], run: F) -> InterruptReport
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<CommandResult, AppError>>,
+    {
+        let mut report = InterruptReport::default();
+        for strategy in strategies {
+            let start = Instant::now();
+            let (success, error, was_muted) = match run(strategy.script().to_string()).await {
+                Ok(res) if strategy.is_success(&res) => (true, None, strategy.was_muted(&res)),
+                Ok(res) => (false, Some(format!("exit code {}", res.exit_code)), false),
+                Err(e) => (false, Some(e.to_string()), false),
+            };
+            report.attempts.push(InterruptAttempt {
+                strategy: *strategy,
+                success,
+                elapsed_ms: start.elapsed().as_millis() as u64,
+                error,
+                was_muted,
+            });
+            if success {
+                break;
+            }
+        }
+        report
+    }
+
+    /// 在本机恢复打断时的副作用（例如取消静音）
+    pub async fn restore(report: &InterruptReport) -> Result<(), AppError> {
+        Interrupter::restore_with(report, |script| async move { run_shell(&script).await }).await
+    }
+
+    pub async fn restore_with<F, Fut>(report: &InterruptReport, run: F) -> Result<(), AppError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<CommandResult, AppError>>,
+    {
+        let Some(attempt) = report.attempts.iter().find(|attempt| attempt.success) else {
+            return Ok(());
+        };
+        if attempt.was_muted {
+            return Ok(());
+        }
+        if let Some(script) = attempt.strategy.restore_script() {
+            run(script.to_string()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn result(stdout: &str, exit_code: i32) -> CommandResult {
+        CommandResult {
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code,
+        }
+    }
+
+    /// 在模拟的音箱上执行脚本，只有 `working` 会成功
+    async fn interrupt(
+        working: InterruptStrategy,
+        scripts: &Mutex<Vec<String>>,
+    ) -> InterruptReport {
+        Interrupter::interrupt_with(&InterruptStrategy::DEFAULT_CHAIN, |script| {
+            scripts.lock().unwrap().push(script.clone());
+            async move {
+                if script == working.script() {
+                    Ok(result("{\"code\":0}\n{\"code\": 0}", 0))
+                } else if script.contains("ubus") {
+                    Err("ubus timed out".into())
+                } else {
+                    Ok(result("", 1))
+                }
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn falls_back_in_order_until_a_strategy_works() {
+        let scripts = Mutex::new(Vec::new());
+        let report = interrupt(InterruptStrategy::MuteOutput, &scripts).await;
+
+        assert_eq!(report.strategy(), Some(InterruptStrategy::MuteOutput));
+        let tried: Vec<InterruptStrategy> = report.attempts.iter().map(|a| a.strategy).collect();
+        assert_eq!(tried, InterruptStrategy::DEFAULT_CHAIN[..3]);
+        assert_eq!(report.attempts[0].error.as_deref(), Some("ubus timed out"));
+        assert_eq!(report.attempts[1].error.as_deref(), Some("exit code 1"));
+        // 不会走到重启服务这一步
+        assert_eq!(scripts.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn reports_failure_when_every_strategy_fails() {
+        let scripts = Mutex::new(Vec::new());
+        let report = Interrupter::interrupt_with(&InterruptStrategy::DEFAULT_CHAIN, |script| {
+            scripts.lock().unwrap().push(script);
+            async { Ok(result("", 1)) }
+        })
+        .await;
+
+        assert!(!report.success());
+        assert_eq!(
+            report.attempts.len(),
+            InterruptStrategy::DEFAULT_CHAIN.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_only_undoes_muting() {
+        let scripts = Mutex::new(Vec::new());
+        let run = |script: String| {
+            scripts.lock().unwrap().push(script);
+            async { Ok(result("", 0)) }
+        };
+
+        let stopped = interrupt(InterruptStrategy::StopPlayer, &Mutex::new(Vec::new())).await;
+        Interrupter::restore_with(&stopped, run).await.unwrap();
+        assert!(scripts.lock().unwrap().is_empty());
+
+        let muted = interrupt(InterruptStrategy::MuteOutput, &Mutex::new(Vec::new())).await;
+        Interrupter::restore_with(&muted, run).await.unwrap();
+        assert_eq!(
+            *scripts.lock().unwrap(),
+            ["amixer -q sset Master unmute 2>&1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn restore_keeps_output_that_was_already_muted() {
+        let report = Interrupter::interrupt_with(&[InterruptStrategy::MuteOutput], |_| async {
+            Ok(result("was_muted\n", 0))
+        })
+        .await;
+        assert!(report.attempts[0].was_muted);
+
+        let scripts = Mutex::new(Vec::new());
+        Interrupter::restore_with(&report, |script: String| {
+            scripts.lock().unwrap().push(script);
+            async { Ok(result("", 0)) }
+        })
+        .await
+        .unwrap();
+        assert!(scripts.lock().unwrap().is_empty());
+    }
+}
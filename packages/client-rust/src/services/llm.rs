@@ -0,0 +1,148 @@
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::base::AppError;
+use crate::services::config::{OpenAIConfig, ProviderConfig};
+use crate::services::retry::{self, Backoff};
+
+/// 等待超过这个时间不如直接换下一个接口，用户还在等回复
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// 按顺序尝试的一组 OpenAI 兼容接口，一个失败时换下一个
+pub struct ProviderChain {
+    client: Client,
+    /// 主接口在最前
+    providers: Vec<ProviderConfig>,
+    /// 遇到 429 或 5xx 时对同一接口重试的次数
+    retries: u32,
+    usage: Mutex<ProviderUsage>,
+}
+
+/// 各接口的使用情况，用于状态接口
+#[derive(Default)]
+struct ProviderUsage {
+    stats: Vec<ProviderStats>,
+    last_served: Option<String>,
+}
+
+#[derive(Default)]
+struct ProviderStats {
+    served: u64,
+    failures: u64,
+    last_error: Option<String>,
+}
+
+impl ProviderChain {
+    pub fn new(config: &OpenAIConfig) -> Self {
+        // 超时按接口在每个请求上设置
+        let client = Client::builder()
+            .build()
+            .expect("Failed to create HTTP client");
+        let providers = config.providers();
+        let usage = ProviderUsage {
+            stats: providers.iter().map(|_| ProviderStats::default()).collect(),
+            last_served: None,
+        };
+        Self {
+            client,
+            providers,
+            retries: config.retries.unwrap_or(1),
+            usage: Mutex::new(usage),
+        }
+    }
+
+    /// 每个接口的计数，用于状态接口
+    pub fn status(&self) -> Value {
+        let usage = self.usage.lock().unwrap();
+        let providers: Vec<Value> = self
+            .providers
+            .iter()
+            .zip(&usage.stats)
+            .map(|(provider, stats)| {
+                json!({
+                    "name": provider.label(),
+                    "model": provider.model,
+                    "served": stats.served,
+                    "failures": stats.failures,
+                    "lastError": stats.last_error,
+                })
+            })
+            .collect();
+        json!({ "lastServed": usage.last_served, "providers": providers })
+    }
+
+    /// 发送 chat completions 请求，一个接口失败时换下一个，返回响应和提供响应的接口名称
+    pub async fn complete(&self, mut body: Value) -> Result<(Value, String), AppError> {
+        let mut errors = Vec::new();
+        for (index, provider) in self.providers.iter().enumerate() {
+            body["model"] = json!(provider.model);
+            let result = self.request(provider, &body).await;
+            let mut usage = self.usage.lock().unwrap();
+            match result {
+                Ok(response) => {
+                    usage.stats[index].served += 1;
+                    usage.last_served = Some(provider.label());
+                    return Ok((response, provider.label()));
+                }
+                Err(e) => {
+                    eprintln!("⚠️  [LLM] provider {} failed: {}", provider.label(), e);
+                    usage.stats[index].failures += 1;
+                    usage.stats[index].last_error = Some(e.to_string());
+                    errors.push(format!("{}: {}", provider.label(), e));
+                }
+            }
+        }
+        Err(format!("All LLM providers failed ({})", errors.join("; ")).into())
+    }
+
+    /// 向一个接口发送请求，遇到限流和服务端错误时重试
+    async fn request(&self, provider: &ProviderConfig, body: &Value) -> Result<Value, AppError> {
+        let url = format!("{}/chat/completions", provider.base_url);
+        let timeout = Duration::from_secs(provider.timeout.unwrap_or(30));
+        let mut backoff = Backoff::new(Duration::from_secs(1), MAX_RETRY_DELAY);
+        let mut attempt = 0;
+
+        loop {
+            let response = self
+                .client
+                .post(&url)
+                .timeout(timeout)
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", provider.api_key))
+                .json(body)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response.json().await.map_err(|e| e.to_string())?);
+            }
+
+            let retryable =
+                status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            let delay = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| retry::retry_after(value, chrono::Utc::now()))
+                .unwrap_or_else(|| backoff.next_delay());
+            let error_text = response.text().await.unwrap_or_default();
+            if !retryable || attempt >= self.retries || delay > MAX_RETRY_DELAY {
+                return Err(format!("LLM API error: HTTP {}: {}", status, error_text).into());
+            }
+
+            println!(
+                "⏳ [LLM] {} returned HTTP {}, retrying in {:.1}s",
+                provider.label(),
+                status,
+                delay.as_secs_f64()
+            );
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
@@ -1,2 +1,18 @@
+pub mod auth;
+pub mod boot;
+pub mod command;
+pub mod config;
 pub mod connect;
+pub mod gate;
+pub mod identity;
+pub mod interrupt;
+pub mod llm;
 pub mod monitor;
+pub mod outbox;
+pub mod retry;
+pub mod speech;
+pub mod router;
+pub mod speaker;
+pub mod status;
+pub mod tools;
+pub mod update;
@@ -36,7 +36,7 @@ impl FileMonitor {
         let file_path_clone = file_path.to_string();
 
         let monitor = tokio::spawn(async move {
-            let _ = FileMonitor::start_monitor(file_path_clone.as_str(), on_update).await;
+            let _ = FileMonitor::watch(file_path_clone.as_str(), on_update).await;
         });
 
         TaskManager::instance()
@@ -50,7 +50,8 @@ impl FileMonitor {
             .await;
     }
 
-    async fn start_monitor<F, Fut>(file_path: &str, on_update: F) -> Result<(), AppError>
+    /// 持续监听文件追加的内容，只有出错时才会返回，适合交给 Supervisor 管理
+    pub async fn watch<F, Fut>(file_path: &str, on_update: F) -> Result<(), AppError>
     where
         F: Fn(FileMonitorEvent) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<(), AppError>> + Send + 'static,
@@ -62,11 +63,11 @@ impl FileMonitor {
         let file = OpenOptions::new().read(true).open(file_path).await?;
         let mut reader = BufReader::new(file);
 
-        let metadata = reader.get_ref().metadata().await.unwrap();
+        let metadata = reader.get_ref().metadata().await?;
         let mut position = metadata.len();
 
         loop {
-            let metadata = reader.get_ref().metadata().await.unwrap();
+            let metadata = reader.get_ref().metadata().await?;
 
             let current_size = metadata.len();
             if current_size < position {
@@ -1,28 +1,77 @@
-use std::future::Future;
-
 use serde::{Deserialize, Serialize};
 
 use crate::base::AppError;
+use crate::utils::event::{EventBus, Topic};
+use crate::utils::task::Supervisor;
 
 use super::file::{FileMonitor, FileMonitorEvent};
 
+/// 最终的语音识别结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecognizedInstruction {
+    pub dialog_id: String,
+    pub text: String,
+    pub confidence: f64,
+}
+
+/// 识别出一句完整的指令，文本不为空
+pub static INSTRUCTION_RECOGNIZED: Topic<RecognizedInstruction> =
+    Topic::new("instruction.recognized");
+/// instruction.log 中的每一条消息
+pub static INSTRUCTION_MESSAGE: Topic<LogMessage> = Topic::new("instruction.message");
+
 pub struct InstructionMonitor;
 
 static INSTRUCTION_FILE_PATH: &str = "/tmp/mico_aivs_lab/instruction.log";
 
 impl InstructionMonitor {
-    pub async fn start<F, Fut>(on_update: F)
-    where
-        F: Fn(FileMonitorEvent) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
-    {
-        FileMonitor::instance()
-            .start(INSTRUCTION_FILE_PATH, on_update)
+    /// 在后台监听，事件发布到 `instruction.*`
+    pub async fn start() {
+        Supervisor::instance()
+            .spawn("instruction", InstructionMonitor::run)
             .await;
     }
 
     pub async fn stop() {
-        FileMonitor::instance().stop(INSTRUCTION_FILE_PATH).await;
+        Supervisor::instance().stop("instruction").await;
+    }
+
+    /// 在当前任务中持续监听，只有出错时才会返回
+    pub async fn run() -> Result<(), AppError> {
+        FileMonitor::watch(INSTRUCTION_FILE_PATH, InstructionMonitor::on_line).await
+    }
+
+    async fn on_line(event: FileMonitorEvent) -> Result<(), AppError> {
+        let FileMonitorEvent::NewLine(content) = event else {
+            return Ok(());
+        };
+        // 忽略无法解析的行
+        let Ok(message) = serde_json::from_str::<LogMessage>(&content) else {
+            return Ok(());
+        };
+
+        if let Payload::RecognizeResultPayload {
+            is_final: true,
+            results,
+            ..
+        } = &message.payload
+        {
+            if let Some(result) = results.first().filter(|r| !r.text.trim().is_empty()) {
+                let recognized = RecognizedInstruction {
+                    dialog_id: message.header.dialog_id.clone(),
+                    text: result.text.clone(),
+                    confidence: result.confidence,
+                };
+                EventBus::instance()
+                    .publish_typed(&INSTRUCTION_RECOGNIZED, &recognized)
+                    .await;
+            }
+        }
+
+        EventBus::instance()
+            .publish_typed(&INSTRUCTION_MESSAGE, &message)
+            .await;
+        Ok(())
     }
 }
 
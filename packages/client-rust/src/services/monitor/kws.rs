@@ -1,19 +1,26 @@
-use std::future::Future;
+use futures::future::BoxFuture;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
 use crate::base::AppError;
+use crate::utils::event::{EventBus, Topic};
+use crate::utils::task::Supervisor;
 
 use super::file::{FileMonitor, FileMonitorEvent};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum KwsMonitorEvent {
-    Started,
-    Keyword(String),
+/// 唤醒词命中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeywordDetected {
+    pub keyword: String,
+    pub timestamp: u64,
 }
 
+/// 唤醒词服务启动，负载是日志里的时间戳
+pub static KWS_STARTED: Topic<u64> = Topic::new("kws.started");
+/// 唤醒词命中
+pub static KWS_KEYWORD: Topic<KeywordDetected> = Topic::new("kws.keyword");
+
 pub struct KwsMonitor;
 
 pub static KWS_FILE_PATH: &str = "/tmp/open-xiaoai/kws.log";
@@ -21,39 +28,51 @@ pub static KWS_FILE_PATH: &str = "/tmp/open-xiaoai/kws.log";
 static LAST_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
 
 impl KwsMonitor {
-    pub async fn start<F, Fut>(on_update: F)
-    where
-        F: Fn(KwsMonitorEvent) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
-    {
-        let on_update = Arc::new(on_update);
-        FileMonitor::instance()
-            .start(KWS_FILE_PATH, move |event| {
-                let on_update = Arc::clone(&on_update);
-                async move {
-                    if let FileMonitorEvent::NewLine(content) = event {
-                        let data = content.split('@').collect::<Vec<&str>>();
-                        let timestamp = data[0].parse::<u64>().unwrap();
-                        let keyword = data[1].to_string();
-                        let last_timestamp = LAST_TIMESTAMP.load(Ordering::Relaxed);
-                        if timestamp != last_timestamp {
-                            LAST_TIMESTAMP.store(timestamp, Ordering::Relaxed);
-                            let kws_event = if keyword == "__STARTED__" {
-                                KwsMonitorEvent::Started
-                            } else {
-                                KwsMonitorEvent::Keyword(keyword)
-                            };
-                            let _ = on_update(kws_event).await;
-                        }
-                    }
-                    Ok(())
-                }
-            })
-            .await;
+    /// 在后台监听，事件发布到 `kws.*`，出错退出后由 Supervisor 重启
+    pub async fn start() {
+        Supervisor::instance().spawn("kws", KwsMonitor::run).await;
     }
 
     pub async fn stop() {
+        Supervisor::instance().stop("kws").await;
+    }
+
+    /// 在当前任务中持续监听，只有出错时才会返回
+    pub async fn run() -> Result<(), AppError> {
         LAST_TIMESTAMP.store(0, Ordering::Relaxed);
-        FileMonitor::instance().stop(KWS_FILE_PATH).await;
+        FileMonitor::watch(KWS_FILE_PATH, KwsMonitor::on_line).await
+    }
+
+    fn on_line(event: FileMonitorEvent) -> BoxFuture<'static, Result<(), AppError>> {
+        Box::pin(async move {
+            let FileMonitorEvent::NewLine(content) = event else {
+                return Ok(());
+            };
+            // 格式：timestamp@keyword，忽略无法解析的行
+            let Some((timestamp, keyword)) = content.split_once('@') else {
+                return Ok(());
+            };
+            let Ok(timestamp) = timestamp.parse::<u64>() else {
+                return Ok(());
+            };
+            let last_timestamp = LAST_TIMESTAMP.load(Ordering::Relaxed);
+            if timestamp != last_timestamp {
+                LAST_TIMESTAMP.store(timestamp, Ordering::Relaxed);
+                if keyword == "__STARTED__" {
+                    EventBus::instance()
+                        .publish_typed(&KWS_STARTED, &timestamp)
+                        .await;
+                } else {
+                    let detected = KeywordDetected {
+                        keyword: keyword.to_string(),
+                        timestamp,
+                    };
+                    EventBus::instance()
+                        .publish_typed(&KWS_KEYWORD, &detected)
+                        .await;
+                }
+            }
+            Ok(())
+        })
     }
 }
@@ -1,59 +1,298 @@
 use serde::{Deserialize, Serialize};
-use std::future::Future;
-use tokio::time::{sleep, Duration};
+use serde_json::Value;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::time::{sleep, timeout, timeout_at, Duration, Instant};
 
 use crate::base::AppError;
+use crate::utils::event::{EventBus, Topic};
 use crate::utils::shell::run_shell;
-use crate::utils::task::TaskManager;
+use crate::utils::task::Supervisor;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
 pub enum PlayingMonitorEvent {
     Playing,
     Paused,
+    #[default]
     Idle,
 }
 
+/// 当前播放的曲目
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct TrackInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// 播放器状态
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct PlayingState {
+    pub status: PlayingMonitorEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<TrackInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<u8>,
+    /// 内容来源，比如音乐、电台、蓝牙
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl PlayingState {
+    /// 除播放进度外是否有变化，进度每时每刻都在变，不单独触发事件
+    fn differs_from(&self, other: &PlayingState) -> bool {
+        self.status != other.status
+            || self.track != other.track
+            || self.volume != other.volume
+            || self.source != other.source
+    }
+}
+
+/// 播放状态发生变化
+pub static PLAYING_CHANGED: Topic<PlayingState> = Topic::new("playing.changed");
+
+/// 轮询间隔：状态刚变化时最短，之后逐渐拉长
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_POLL_INTERVAL_PLAYING: Duration = Duration::from_secs(2);
+const MAX_POLL_INTERVAL_IDLE: Duration = Duration::from_secs(5);
+/// 监听 ubus 事件时兜底的刷新间隔
+const EVENT_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+/// 一次状态变化往往伴随一串事件，合并后只查询一次
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(100);
+/// 事件一直不停时，最多合并这么久就查询一次
+const EVENT_DEBOUNCE_MAX: Duration = Duration::from_secs(1);
+
 pub struct PlayingMonitor;
 
 impl PlayingMonitor {
-    pub async fn start<F, Fut>(on_update: F)
-    where
-        F: Fn(PlayingMonitorEvent) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
-    {
-        let monitor = tokio::spawn(async move {
-            let _ = PlayingMonitor::start_monitor(on_update).await;
-        });
-
-        TaskManager::instance().add("PlayingMonitor", monitor).await;
+    /// 在后台监听，事件发布到 `playing.changed`，出错退出后由 Supervisor 重启
+    pub async fn start() {
+        Supervisor::instance()
+            .spawn("playing", PlayingMonitor::run)
+            .await;
     }
 
     pub async fn stop() {
-        TaskManager::instance().dispose("PlayingMonitor").await;
+        Supervisor::instance().stop("playing").await;
     }
 
-    async fn start_monitor<F, Fut>(on_update: F) -> Result<(), AppError>
-    where
-        F: Fn(PlayingMonitorEvent) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
-    {
-        let mut last_status = PlayingMonitorEvent::Idle;
+    /// 在当前任务中持续监听，只有出错时才会返回
+    ///
+    /// 优先订阅 ubus 的 mediaplayer 事件，有事件时才查询状态；
+    /// ubus listen 不可用时退化为自适应间隔的轮询
+    pub async fn run() -> Result<(), AppError> {
+        let mut events = PlayerEvents::listen().ok();
+        if events.is_none() {
+            eprintln!("⚠️  [PlayingMonitor] ubus listen unavailable, falling back to polling");
+        }
+
+        let mut last_state = PlayingState::default();
+        let mut interval = MIN_POLL_INTERVAL;
         loop {
-            let res = run_shell("mphelper mute_stat").await?;
-            let status = if res.stdout.contains("1") {
-                PlayingMonitorEvent::Playing
-            } else if res.stdout.contains("2") {
-                PlayingMonitorEvent::Paused
+            let state = PlayingMonitor::query().await?;
+            if state.differs_from(&last_state) {
+                EventBus::instance()
+                    .publish_typed(&PLAYING_CHANGED, &state)
+                    .await;
+                interval = MIN_POLL_INTERVAL;
             } else {
-                PlayingMonitorEvent::Idle
+                let max = if state.status == PlayingMonitorEvent::Idle {
+                    MAX_POLL_INTERVAL_IDLE
+                } else {
+                    MAX_POLL_INTERVAL_PLAYING
+                };
+                interval = (interval * 2).min(max);
+            }
+            last_state = state;
+
+            let Some(player_events) = events.as_mut() else {
+                sleep(interval).await;
+                continue;
             };
+            match timeout(EVENT_REFRESH_INTERVAL, player_events.next()).await {
+                // 超时后兜底刷新一次
+                Err(_) => {}
+                Ok(true) => player_events.debounce().await,
+                Ok(false) => {
+                    eprintln!("⚠️  [PlayingMonitor] ubus listen exited, falling back to polling");
+                    events = None;
+                }
+            }
+        }
+    }
+
+    /// 查询当前的播放器状态
+    pub async fn query() -> Result<PlayingState, AppError> {
+        let state = PlayingMonitor::query_mediaplayer().await.ok();
+        match state {
+            Some(state) => Ok(state),
+            // 部分固件没有 player_get_play_status，只能拿到播放状态
+            None => PlayingMonitor::query_mute_stat().await,
+        }
+    }
+
+    async fn query_mediaplayer() -> Result<PlayingState, AppError> {
+        let output = Command::new("ubus")
+            .args(["-t1", "call", "mediaplayer", "player_get_play_status"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .await?;
+        let res: Value = serde_json::from_slice(&output.stdout)?;
+        if res.get("code").and_then(Value::as_i64) != Some(0) {
+            return Err(format!("player_get_play_status failed: {}", res).into());
+        }
+        // info 是一段 JSON 字符串
+        let info = match res.get("info") {
+            Some(Value::String(info)) => serde_json::from_str::<Value>(info)?,
+            Some(info) => info.clone(),
+            None => return Err("player_get_play_status returned no info".into()),
+        };
+        Ok(parse_play_status(&info))
+    }
+
+    async fn query_mute_stat() -> Result<PlayingState, AppError> {
+        let res = run_shell("mphelper mute_stat").await?;
+        let status = if res.stdout.contains("1") {
+            PlayingMonitorEvent::Playing
+        } else if res.stdout.contains("2") {
+            PlayingMonitorEvent::Paused
+        } else {
+            PlayingMonitorEvent::Idle
+        };
+        Ok(PlayingState {
+            status,
+            ..Default::default()
+        })
+    }
+}
 
-            if last_status != status {
-                last_status = status.clone();
-                let _ = on_update(status).await;
+fn parse_play_status(info: &Value) -> PlayingState {
+    let status = match info.get("status").and_then(Value::as_i64) {
+        Some(1) => PlayingMonitorEvent::Playing,
+        Some(2) => PlayingMonitorEvent::Paused,
+        _ => PlayingMonitorEvent::Idle,
+    };
+    let detail = info.get("play_song_detail");
+    let track = detail
+        .map(|detail| TrackInfo {
+            id: get_string(detail, "audio_id"),
+            title: get_string(detail, "title"),
+            artist: get_string(detail, "artist"),
+            album: get_string(detail, "album"),
+            duration_ms: get_u64(detail, "duration"),
+        })
+        .filter(|track| track != &TrackInfo::default());
+    let source = detail
+        .and_then(|detail| get_string(detail, "cp_origin"))
+        .or_else(|| get_string(info, "media_type"));
+    PlayingState {
+        status,
+        track,
+        position_ms: detail.and_then(|detail| get_u64(detail, "position")),
+        volume: get_u64(info, "volume").map(|v| v.min(100) as u8),
+        source,
+    }
+}
+
+/// 字段有时是字符串，有时是数字
+fn get_string(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn get_u64(value: &Value, key: &str) -> Option<u64> {
+    match value.get(key)? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// `ubus listen` 的输出，每行一个事件
+struct PlayerEvents {
+    _child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl PlayerEvents {
+    fn listen() -> Result<Self, AppError> {
+        PlayerEvents::spawn("ubus", &["listen"])
+    }
+
+    fn spawn(program: &str, args: &[&str]) -> Result<Self, AppError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or("ubus listen has no stdout")?;
+        Ok(Self {
+            _child: child,
+            lines: BufReader::new(stdout).lines(),
+        })
+    }
+
+    /// 等到下一个播放器相关的事件，进程退出时返回 false
+    async fn next(&mut self) -> bool {
+        loop {
+            match self.lines.next_line().await {
+                Ok(Some(line)) if is_player_event(&line) => return true,
+                Ok(Some(_)) => continue,
+                _ => return false,
             }
+        }
+    }
 
-            sleep(Duration::from_millis(10)).await;
+    /// 吞掉紧随其后的一串事件，最多等待 EVENT_DEBOUNCE_MAX
+    async fn debounce(&mut self) {
+        let deadline = Instant::now() + EVENT_DEBOUNCE_MAX;
+        while Instant::now() < deadline {
+            let quiet_until = (Instant::now() + EVENT_DEBOUNCE).min(deadline);
+            if !matches!(
+                timeout_at(quiet_until, self.lines.next_line()).await,
+                Ok(Ok(Some(_)))
+            ) {
+                break;
+            }
         }
     }
 }
+
+fn is_player_event(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    line.contains("player") || line.contains("volume")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn debounce_gives_up_on_an_endless_burst() {
+        let script = "while :; do echo player; sleep 0.01; done";
+        let mut events = PlayerEvents::spawn("sh", &["-c", script]).unwrap();
+        assert!(events.next().await);
+
+        let started = Instant::now();
+        timeout(Duration::from_secs(5), events.debounce())
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= EVENT_DEBOUNCE_MAX);
+    }
+}
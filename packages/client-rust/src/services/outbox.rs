@@ -0,0 +1,187 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::services::connect::data::Event;
+
+/// 暂时发不出去的事件，按顺序保存在磁盘上（每行一个 JSON），重启后不丢失
+///
+/// 超过容量时丢弃最早的事件
+pub struct Outbox {
+    path: PathBuf,
+    capacity: usize,
+    events: Mutex<VecDeque<Event>>,
+}
+
+impl Outbox {
+    /// 打开 outbox，读取上次没发出去的事件，无法解析的行会被跳过
+    pub fn open(path: impl AsRef<Path>, capacity: usize) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let capacity = capacity.max(1);
+        let mut events: VecDeque<Event> = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        while events.len() > capacity {
+            events.pop_front();
+        }
+        Self {
+            path,
+            capacity,
+            events: Mutex::new(events),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.lock().unwrap().is_empty()
+    }
+
+    /// 加入队尾，返回因容量限制被丢弃的事件
+    pub fn push(&self, event: Event) -> Option<Event> {
+        let mut events = self.events.lock().unwrap();
+        events.push_back(event);
+        let dropped = if events.len() > self.capacity {
+            events.pop_front()
+        } else {
+            None
+        };
+        self.persist(&events);
+        dropped
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|event| event.id == id)
+    }
+
+    /// 最早的事件
+    pub fn front(&self) -> Option<Event> {
+        self.events.lock().unwrap().front().cloned()
+    }
+
+    /// 事件发送成功后移除
+    pub fn remove(&self, id: &str) {
+        let mut events = self.events.lock().unwrap();
+        let before = events.len();
+        events.retain(|event| event.id != id);
+        if events.len() != before {
+            self.persist(&events);
+        }
+    }
+
+    /// 先写临时文件再改名，避免断电留下半个文件
+    fn persist(&self, events: &VecDeque<Event>) {
+        let write = || -> std::io::Result<()> {
+            if events.is_empty() {
+                return match std::fs::remove_file(&self.path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                };
+            }
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let tmp = self.path.with_extension("tmp");
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+            for event in events {
+                serde_json::to_writer(&mut file, event)?;
+                file.write_all(b"\n")?;
+            }
+            file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            std::fs::rename(&tmp, &self.path)
+        };
+        if let Err(e) = write() {
+            eprintln!("⚠️  [Outbox] failed to save {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// 最近执行过的指令 id。服务端在收到 ack 之前会重复下发指令，ack 丢失时不能把指令执行两次
+///
+/// id 每行一个保存在 outbox 旁边，重启后依然有效
+pub struct HandledCommands {
+    path: PathBuf,
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl HandledCommands {
+    /// 记住的 id 数量，远多于两次轮询之间可能排队的指令
+    pub const CAPACITY: usize = 256;
+
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let mut handled = Self {
+            path: path.as_ref().to_path_buf(),
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        };
+        for id in std::fs::read_to_string(&handled.path)
+            .unwrap_or_default()
+            .lines()
+        {
+            handled.remember(id.trim());
+        }
+        handled
+    }
+
+    /// 指令已经执行过时返回 false
+    pub fn insert(&mut self, id: &str) -> bool {
+        if !self.remember(id) {
+            return false;
+        }
+        self.persist();
+        true
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    fn remember(&mut self, id: &str) -> bool {
+        if id.is_empty() || !self.ids.insert(id.to_string()) {
+            return false;
+        }
+        self.order.push_back(id.to_string());
+        if self.order.len() > Self::CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// 先写临时文件再改名，避免断电留下半个文件
+    fn persist(&self) {
+        let write = || -> std::io::Result<()> {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let tmp = self.path.with_extension("tmp");
+            let mut content = self
+                .order
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n");
+            content.push('\n');
+            std::fs::write(&tmp, content)?;
+            std::fs::rename(&tmp, &self.path)
+        };
+        if let Err(e) = write() {
+            eprintln!(
+                "⚠️  [Outbox] failed to save handled commands to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 指数退避，每次等待时间翻倍，并在后一半随机抖动，避免多台音箱同时重试
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// 下一次重试前的等待时间
+    pub fn next_delay(&mut self) -> Duration {
+        let ceiling = self
+            .base
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+        let half = ceiling / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+
+    /// 成功后从头开始
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// 熔断器：连续失败达到阈值后断开一段时间，期间直接拒绝请求，
+/// 冷却结束后放行请求试探，成功则恢复
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// 是否允许发出请求
+    pub fn allow(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.open_until.is_none_or(|until| Instant::now() >= until)
+    }
+
+    /// 是否处于断开状态（包括冷却结束、等待试探的状态）
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    /// 记录一次失败，返回熔断器是否因此断开
+    pub fn record_failure(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures < self.threshold {
+            return false;
+        }
+        // 试探失败时重新计时
+        state.open_until = Some(Instant::now() + self.cooldown);
+        true
+    }
+}
+
+/// 解析 Retry-After 头，支持秒数和 HTTP 日期两种格式
+pub fn retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // 已经过去的时间表示可以立即重试
+    Some((at.to_utc() - now).to_std().unwrap_or_default())
+}
@@ -0,0 +1,208 @@
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::base::AppError;
+use crate::services::speaker::SpeakerAction;
+
+/// 决定一条语音指令交给谁处理
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RouterConfig {
+    #[serde(default)]
+    pub rules: Vec<RouteRule>,
+    /// 没有规则命中时的处理方式
+    #[serde(default = "RouterConfig::default_action")]
+    pub default: RouteAction,
+}
+
+impl RouterConfig {
+    fn default_action() -> RouteAction {
+        RouteAction::Llm
+    }
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            default: RouterConfig::default_action(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RouteRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "match")]
+    pub matcher: RouteMatcher,
+    pub action: RouteAction,
+    /// 是否打断小爱的原生回复，不填时除 xiaoai 外都会打断
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interrupt: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteMatcher {
+    /// 以任一前缀开头
+    Prefix(Vec<String>),
+    /// 匹配正则表达式
+    Regex(String),
+    /// 包含任一关键词
+    Keyword(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RouteAction {
+    /// 交给原生小爱处理
+    Xiaoai,
+    /// 交给大模型处理
+    Llm,
+    /// 执行音箱动作
+    Speaker { command: SpeakerAction },
+    /// 调用 webhook，返回 JSON 中的 reply 字段会被播报
+    Webhook {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout: Option<u64>,
+    },
+}
+
+impl RouteAction {
+    fn default_interrupt(&self) -> bool {
+        !matches!(self, RouteAction::Xiaoai)
+    }
+}
+
+/// 路由结果
+#[derive(Debug, Clone)]
+pub struct Route<'a> {
+    pub rule: Option<&'a str>,
+    pub action: &'a RouteAction,
+    pub interrupt: bool,
+}
+
+enum CompiledMatcher {
+    Prefix(Vec<String>),
+    Regex(Regex),
+    Keyword(Vec<String>),
+}
+
+impl CompiledMatcher {
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            CompiledMatcher::Prefix(prefixes) => prefixes.iter().any(|p| text.starts_with(p)),
+            CompiledMatcher::Regex(regex) => regex.is_match(text),
+            CompiledMatcher::Keyword(keywords) => keywords.iter().any(|k| text.contains(k)),
+        }
+    }
+}
+
+pub struct Router {
+    config: RouterConfig,
+    matchers: Vec<CompiledMatcher>,
+}
+
+impl Router {
+    pub fn new(config: RouterConfig) -> Result<Self, AppError> {
+        let mut matchers = Vec::with_capacity(config.rules.len());
+        for (index, rule) in config.rules.iter().enumerate() {
+            let matcher = match &rule.matcher {
+                RouteMatcher::Prefix(prefixes) => CompiledMatcher::Prefix(prefixes.clone()),
+                RouteMatcher::Keyword(keywords) => CompiledMatcher::Keyword(keywords.clone()),
+                RouteMatcher::Regex(pattern) => match Regex::new(pattern) {
+                    Ok(regex) => CompiledMatcher::Regex(regex),
+                    Err(e) => {
+                        let name = rule.name.clone().unwrap_or_else(|| index.to_string());
+                        return Err(format!("router rule {}: invalid regex: {}", name, e).into());
+                    }
+                },
+            };
+            matchers.push(matcher);
+        }
+        Ok(Self { config, matchers })
+    }
+
+    /// 按顺序匹配规则，第一条命中的规则生效
+    pub fn route(&self, text: &str) -> Route<'_> {
+        let text = text.trim();
+        for (rule, matcher) in self.config.rules.iter().zip(&self.matchers) {
+            if matcher.is_match(text) {
+                return Route {
+                    rule: rule.name.as_deref(),
+                    action: &rule.action,
+                    interrupt: rule
+                        .interrupt
+                        .unwrap_or_else(|| rule.action.default_interrupt()),
+                };
+            }
+        }
+        Route {
+            rule: None,
+            action: &self.config.default,
+            interrupt: self.config.default.default_interrupt(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn router() -> Router {
+        let config: RouterConfig = serde_json::from_value(json!({
+            "rules": [
+                {"name": "stock", "match": {"keyword": ["闹钟", "天气"]}, "action": {"type": "xiaoai"}},
+                {"name": "pause", "match": {"regex": "^(暂停|停止)播放$"}, "action": {"type": "speaker", "command": {"action": "pause"}}},
+                {"name": "hook", "match": {"prefix": ["请"]}, "action": {"type": "webhook", "url": "http://127.0.0.1/hook"}, "interrupt": false}
+            ],
+            "default": {"type": "llm"}
+        }))
+        .unwrap();
+        Router::new(config).unwrap()
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let router = router();
+
+        let route = router.route("请明天早上设个闹钟");
+        assert_eq!(route.rule, Some("stock"));
+        assert!(matches!(route.action, RouteAction::Xiaoai));
+        assert!(!route.interrupt);
+
+        let route = router.route(" 暂停播放 ");
+        assert_eq!(route.rule, Some("pause"));
+        assert!(route.interrupt);
+
+        let route = router.route("请讲个故事");
+        assert_eq!(route.rule, Some("hook"));
+        assert!(!route.interrupt);
+    }
+
+    #[test]
+    fn unmatched_text_uses_the_default() {
+        let router = router();
+        let route = router.route("暂停播放音乐");
+        assert_eq!(route.rule, None);
+        assert!(matches!(route.action, RouteAction::Llm));
+        assert!(route.interrupt);
+    }
+
+    #[test]
+    fn invalid_regex_names_the_rule() {
+        let config: RouterConfig = serde_json::from_value(json!({
+            "rules": [{"name": "broken", "match": {"regex": "("}, "action": {"type": "llm"}}]
+        }))
+        .unwrap();
+        let error = Router::new(config).err().unwrap().to_string();
+        assert!(
+            error.starts_with("router rule broken: invalid regex"),
+            "{}",
+            error
+        );
+    }
+}
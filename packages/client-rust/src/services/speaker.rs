@@ -1,11 +1,103 @@
-use serde_json::json;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
-use crate::utils::shell::CommandResult;
-use crate::{base::AppError, services::connect::rpc::RPC};
+use crate::services::interrupt::{InterruptReport, InterruptStrategy, Interrupter};
+use crate::utils::event::{EventBus, Topic};
+use crate::utils::shell::{self, CommandResult};
+use crate::{
+    base::{AppError, VERSION},
+    services::connect::{data::Response, rpc::RPC},
+};
+
+/// 可以在配置中引用的音箱动作
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SpeakerAction {
+    Play,
+    Pause,
+    PlayUrl { url: String },
+    PlayText { text: String },
+    MicOn,
+    MicOff,
+    WakeUp,
+    SetVolume { volume: u8 },
+    StepVolume { delta: i32 },
+    Mute,
+    Unmute,
+}
+
+impl SpeakerAction {
+    pub async fn execute(&self) -> Result<bool, AppError> {
+        match self {
+            SpeakerAction::Play => SpeakerManager::play().await,
+            SpeakerAction::Pause => SpeakerManager::pause().await,
+            SpeakerAction::PlayUrl { url } => SpeakerManager::play_url(url).await,
+            SpeakerAction::PlayText { text } => SpeakerManager::play_text(text).await,
+            SpeakerAction::MicOn => SpeakerManager::mic_on().await,
+            SpeakerAction::MicOff => SpeakerManager::mic_off().await,
+            SpeakerAction::WakeUp => SpeakerManager::wake_up().await,
+            SpeakerAction::SetVolume { volume } => SpeakerManager::set_volume(*volume).await,
+            SpeakerAction::StepVolume { delta } => {
+                SpeakerManager::step_volume(*delta).await.map(|_| true)
+            }
+            SpeakerAction::Mute => SpeakerManager::mute().await,
+            SpeakerAction::Unmute => SpeakerManager::unmute().await,
+        }
+    }
+}
+
+/// 音量状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeState {
+    /// 媒体音量 0-100
+    pub volume: u8,
+    pub muted: bool,
+    /// 助手说话时使用的音量，未设置时跟随媒体音量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assistant_volume: Option<u8>,
+}
+
+/// 音量或静音状态发生变化
+pub static VOLUME_CHANGED: Topic<VolumeState> = Topic::new("volume.changed");
+
+/// 设备信息，一次 shell 调用采集，拿不到的字段为空
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub firmware: Option<String>,
+    pub boot_partition: Option<String>,
+    pub uptime_secs: Option<u64>,
+    pub mem_total_kb: Option<u64>,
+    pub mem_available_kb: Option<u64>,
+    /// 1、5、15 分钟平均负载
+    pub load: Option<[f32; 3]>,
+    /// /data 分区剩余空间
+    pub data_free_kb: Option<u64>,
+    pub wifi_ssid: Option<String>,
+    /// Wi-Fi 信号强度 dBm
+    pub wifi_rssi: Option<i32>,
+    pub mic_on: Option<bool>,
+    /// client 版本
+    pub version: String,
+}
 
 pub struct SpeakerManager;
 
+static LOCAL_SHELL: AtomicBool = AtomicBool::new(false);
+
+/// 助手音量，NO_ASSISTANT_VOLUME 表示未设置
+static ASSISTANT_VOLUME: AtomicU8 = AtomicU8::new(NO_ASSISTANT_VOLUME);
+const NO_ASSISTANT_VOLUME: u8 = u8::MAX;
+
 impl SpeakerManager {
+    /// 运行在音箱本机时直接执行命令，而不是通过 RPC 转发给 client
+    pub fn use_local_shell(enabled: bool) {
+        LOCAL_SHELL.store(enabled, Ordering::Relaxed);
+    }
+
     /// 获取启动分区
     pub async fn get_boot() -> Result<String, AppError> {
         const COMMAND: &str = r#"
@@ -15,8 +107,11 @@ impl SpeakerManager {
         Ok(res.stdout.trim().to_string())
     }
 
-    /// 设置启动分区
+    /// 设置启动分区，只接受 boot0 / boot1
     pub async fn set_boot(boot_part: &str) -> Result<bool, AppError> {
+        if !matches!(boot_part, "boot0" | "boot1") {
+            return Err(format!("invalid boot partition: {:?}", boot_part).into());
+        }
         const COMMAND: &str = r#"
             fw_env -s boot_part %s >/dev/null 2>&1 && echo $(fw_env -g boot_part)
         "#;
@@ -25,6 +120,15 @@ impl SpeakerManager {
         Ok(res.stdout.contains(boot_part))
     }
 
+    /// 重启设备
+    pub async fn reboot() -> Result<(), AppError> {
+        const COMMAND: &str = r#"
+            reboot
+        "#;
+        SpeakerManager::run_shell(COMMAND).await?;
+        Ok(())
+    }
+
     /// 获取设备型号
     pub async fn get_device_model() -> Result<String, AppError> {
         const COMMAND: &str = r#"
@@ -43,6 +147,27 @@ impl SpeakerManager {
         Ok(res.stdout.trim().to_string())
     }
 
+    /// 获取设备信息
+    pub async fn get_device_info() -> Result<DeviceInfo, AppError> {
+        const COMMAND: &str = r#"
+            echo "model=$(micocfg_model 2>/dev/null)"
+            echo "serial=$(micocfg_sn 2>/dev/null)"
+            echo "firmware=$(grep -m1 -i version /usr/share/mico/version 2>/dev/null)"
+            echo "boot=$(fw_env -g boot_part 2>/dev/null)"
+            echo "uptime=$(cut -d' ' -f1 /proc/uptime)"
+            echo "mem_total=$(awk '/^MemTotal:/ {print $2}' /proc/meminfo)"
+            echo "mem_available=$(awk '/^MemAvailable:/ {print $2}' /proc/meminfo)"
+            echo "mem_free=$(awk '/^MemFree:/ {print $2}' /proc/meminfo)"
+            echo "load=$(cut -d' ' -f1-3 /proc/loadavg)"
+            echo "data_free=$(df -k /data 2>/dev/null | awk 'NR==2 {print $4}')"
+            echo "ssid=$(wpa_cli -i wlan0 status 2>/dev/null | sed -n 's/^ssid=//p')"
+            echo "rssi=$(awk '/wlan0:/ {print $4}' /proc/net/wireless 2>/dev/null)"
+            [ ! -f /tmp/mipns/mute ] && echo "mic=on" || echo "mic=off"
+        "#;
+        let res = SpeakerManager::run_shell(COMMAND).await?;
+        Ok(parse_device_info(&res.stdout))
+    }
+
     /// 获取播放状态
     pub async fn get_play_status() -> Result<String, AppError> {
         const COMMAND: &str = r#"
@@ -77,22 +202,232 @@ impl SpeakerManager {
         Ok(res.stdout.contains("\"code\": 0"))
     }
 
-    /// TTS
+    /// TTS，使用助手音量播报
     pub async fn play_text(text: &str) -> Result<bool, AppError> {
+        let script = format!("/usr/sbin/tts_play.sh {}", shell::quote(text));
+        let previous = SpeakerManager::begin_assistant_voice().await?;
+        let res = SpeakerManager::run_shell(&script)
+            .await
+            .map_err(|e| e.to_string());
+        SpeakerManager::end_assistant_voice(previous).await?;
+        Ok(res?.stdout.contains("\"code\": 0"))
+    }
+
+    /// 获取媒体音量
+    pub async fn get_volume() -> Result<u8, AppError> {
+        const COMMAND: &str = r#"
+            ubus -t1 -S call mediaplayer player_get_play_status 2>&1
+        "#;
+        let res = SpeakerManager::run_shell(COMMAND).await?;
+        let status = serde_json::from_str::<Value>(res.stdout.trim())?;
+        // info 是一段 JSON 字符串
+        let info = match status.get("info") {
+            Some(Value::String(info)) => serde_json::from_str::<Value>(info)?,
+            Some(info) => info.clone(),
+            None => return Err(format!("unexpected play status: {}", res.stdout).into()),
+        };
+        match info.get("volume") {
+            Some(Value::Number(volume)) => volume.as_u64(),
+            Some(Value::String(volume)) => volume.parse().ok(),
+            _ => None,
+        }
+        .map(|volume| volume.min(100) as u8)
+        .ok_or_else(|| format!("play status has no volume: {}", info).into())
+    }
+
+    /// 设置媒体音量 0-100
+    pub async fn set_volume(volume: u8) -> Result<bool, AppError> {
+        let ok = SpeakerManager::write_volume(volume).await?;
+        if ok {
+            SpeakerManager::publish_volume().await;
+        }
+        Ok(ok)
+    }
+
+    /// 按步长调整媒体音量，返回调整后的音量
+    pub async fn step_volume(delta: i32) -> Result<u8, AppError> {
+        let current = SpeakerManager::get_volume().await? as i32;
+        let volume = (current + delta).clamp(0, 100) as u8;
+        if !SpeakerManager::set_volume(volume).await? {
+            return Err(format!("failed to set volume to {}", volume).into());
+        }
+        Ok(volume)
+    }
+
+    /// 是否静音
+    pub async fn is_muted() -> Result<bool, AppError> {
+        const COMMAND: &str = r#"
+            amixer sget Master 2>&1
+        "#;
+        let res = SpeakerManager::run_shell(COMMAND).await?;
+        Ok(res.stdout.contains("[off]"))
+    }
+
+    /// 静音
+    pub async fn mute() -> Result<bool, AppError> {
+        const COMMAND: &str = r#"
+            amixer -q sset Master mute 2>&1
+        "#;
+        let res = SpeakerManager::run_shell(COMMAND).await?;
+        let ok = res.exit_code == 0;
+        if ok {
+            SpeakerManager::publish_volume().await;
+        }
+        Ok(ok)
+    }
+
+    /// 取消静音
+    pub async fn unmute() -> Result<bool, AppError> {
+        const COMMAND: &str = r#"
+            amixer -q sset Master unmute 2>&1
+        "#;
+        let res = SpeakerManager::run_shell(COMMAND).await?;
+        let ok = res.exit_code == 0;
+        if ok {
+            SpeakerManager::publish_volume().await;
+        }
+        Ok(ok)
+    }
+
+    /// 获取助手音量
+    pub fn get_assistant_volume() -> Option<u8> {
+        match ASSISTANT_VOLUME.load(Ordering::Relaxed) {
+            NO_ASSISTANT_VOLUME => None,
+            volume => Some(volume),
+        }
+    }
+
+    /// 设置助手说话时的音量，None 表示跟随媒体音量
+    pub async fn set_assistant_volume(volume: Option<u8>) {
+        let volume = volume.map_or(NO_ASSISTANT_VOLUME, |volume| volume.min(100));
+        ASSISTANT_VOLUME.store(volume, Ordering::Relaxed);
+        SpeakerManager::publish_volume().await;
+    }
+
+    /// 获取完整的音量状态
+    pub async fn volume_state() -> Result<VolumeState, AppError> {
+        let volume = SpeakerManager::get_volume().await?;
+        let muted = SpeakerManager::is_muted().await?;
+        Ok(VolumeState {
+            volume,
+            muted,
+            assistant_volume: SpeakerManager::get_assistant_volume(),
+        })
+    }
+
+    /// 切换到助手音量，返回需要恢复的媒体音量
+    pub async fn begin_assistant_voice() -> Result<Option<u8>, AppError> {
+        let Some(assistant) = SpeakerManager::get_assistant_volume() else {
+            return Ok(None);
+        };
+        let volume = SpeakerManager::get_volume().await?;
+        if volume == assistant {
+            return Ok(None);
+        }
+        SpeakerManager::write_volume(assistant).await?;
+        Ok(Some(volume))
+    }
+
+    /// 恢复 begin_assistant_voice 之前的媒体音量
+    pub async fn end_assistant_voice(previous: Option<u8>) -> Result<(), AppError> {
+        if let Some(volume) = previous {
+            SpeakerManager::write_volume(volume).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_volume(volume: u8) -> Result<bool, AppError> {
         const COMMAND: &str = r#"
-            /usr/sbin/tts_play.sh '%s'
+            ubus -t1 -S call mediaplayer player_set_volume '{"volume":%d}' 2>&1
         "#;
-        let script = COMMAND.replace("%s", text);
+        let script = COMMAND.replace("%d", &volume.min(100).to_string());
         let res = SpeakerManager::run_shell(&script).await?;
-        Ok(res.stdout.contains("\"code\": 0"))
+        Ok(res.stdout.contains("\"code\":0"))
+    }
+
+    async fn publish_volume() {
+        let state = SpeakerManager::volume_state()
+            .await
+            .map_err(|e| e.to_string());
+        match state {
+            Ok(state) => {
+                EventBus::instance()
+                    .publish_typed(&VOLUME_CHANGED, &state)
+                    .await
+            }
+            Err(e) => eprintln!("⚠️  [SpeakerManager] failed to read volume: {}", e),
+        }
+    }
+
+    /// 注册音箱相关的 RPC 命令
+    pub async fn register_commands() {
+        let rpc = RPC::instance();
+        rpc.add_command("get_device_info", |_| async {
+            Ok(Response::from_data(json!(
+                SpeakerManager::get_device_info().await?
+            )))
+        })
+        .await;
+        rpc.add_command("get_volume", |_| async {
+            Ok(Response::from_data(json!(
+                SpeakerManager::volume_state().await?
+            )))
+        })
+        .await;
+        rpc.add_command("set_volume", |request| async move {
+            let volume = request
+                .params
+                .get("volume")
+                .and_then(Value::as_u64)
+                .ok_or("missing volume")?;
+            let ok = SpeakerManager::set_volume(volume.min(100) as u8).await?;
+            Ok(Response::from_data(json!(ok)))
+        })
+        .await;
+        rpc.add_command("step_volume", |request| async move {
+            let delta = request
+                .params
+                .get("delta")
+                .and_then(Value::as_i64)
+                .ok_or("missing delta")?;
+            let volume = SpeakerManager::step_volume(delta.clamp(-100, 100) as i32).await?;
+            Ok(Response::from_data(json!(volume)))
+        })
+        .await;
+        rpc.add_command("mute", |_| async {
+            Ok(Response::from_data(json!(SpeakerManager::mute().await?)))
+        })
+        .await;
+        rpc.add_command("unmute", |_| async {
+            Ok(Response::from_data(json!(SpeakerManager::unmute().await?)))
+        })
+        .await;
+        rpc.add_command("play_url", |request| async move {
+            let url = request
+                .params
+                .get("url")
+                .and_then(Value::as_str)
+                .ok_or("missing url")?;
+            Ok(Response::from_data(json!(
+                SpeakerManager::play_url(url).await?
+            )))
+        })
+        .await;
+        rpc.add_command("set_assistant_volume", |request| async move {
+            let volume = request
+                .params
+                .get("volume")
+                .and_then(Value::as_u64)
+                .map(|volume| volume.min(100) as u8);
+            SpeakerManager::set_assistant_volume(volume).await;
+            Ok(Response::success())
+        })
+        .await;
     }
 
     /// 播放音频
     pub async fn play_url(url: &str) -> Result<bool, AppError> {
-        const COMMAND: &str = r#"
-            ubus call mediaplayer player_play_url '{"url":"%s","type": 1}'
-        "#;
-        let script = COMMAND.replace("%s", url);
+        let script = SpeakerManager::play_url_script(url);
         let res = SpeakerManager::run_shell(&script).await?;
         Ok(res.stdout.contains("\"code\": 0"))
     }
@@ -131,44 +466,173 @@ impl SpeakerManager {
 
     /// 执行命令
     pub async fn ask_xiaoai(text: &str) -> Result<bool, AppError> {
-        const COMMAND: &str = r#"
-            ubus call mibrain ai_service '{"tts":1,"nlp":1,"nlp_text":"%s"}'
-        "#;
-        let script = COMMAND.replace("%s", text);
+        let script = SpeakerManager::ask_xiaoai_script(text);
         let res = SpeakerManager::run_shell(&script).await?;
         Ok(res.stdout.contains("\"code\": 0"))
     }
 
-    /// 中断运行
-    pub async fn abort_xiaoai() -> Result<bool, AppError> {
-        const COMMAND: &str = r#"
-            /etc/init.d/mico_aivs_lab restart >/dev/null 2>&1
-        "#;
-        let res = SpeakerManager::run_shell(COMMAND).await?;
-        Ok(res.exit_code == 0)
+    /// 中断运行，按 InterruptStrategy::DEFAULT_CHAIN 依次尝试，重启服务只作为兜底
+    pub async fn abort_xiaoai() -> Result<InterruptReport, AppError> {
+        let report =
+            Interrupter::interrupt_with(&InterruptStrategy::DEFAULT_CHAIN, |script| async move {
+                SpeakerManager::run_shell(&script).await
+            })
+            .await;
+        if !report.success() {
+            return Err(format!("failed to interrupt xiaoai: {}", report).into());
+        }
+        Ok(report)
     }
 
-    /// 唤醒
-    pub async fn wake_up(flag: bool) -> Result<bool, AppError> {
-        let command = if flag {
-            r#"
-                ubus call pnshelper event_notify '{"src":1,"event":0}'
-            "#
-        } else {
-            r#"
-                ubus call pnshelper event_notify '{"src":3, "event":7}'
-                sleep 0.1
-                ubus call pnshelper event_notify '{"src":3, "event":8}'
-            "#
-        };
-        let res = SpeakerManager::run_shell(command).await?;
+    /// 恢复中断时的副作用（例如取消静音）
+    pub async fn restore_xiaoai(report: &InterruptReport) -> Result<(), AppError> {
+        Interrupter::restore_with(report, |script| async move {
+            SpeakerManager::run_shell(&script).await
+        })
+        .await
+    }
+
+    /// 连续对话：重新唤醒小爱开始收音，和说出唤醒词一样
+    pub async fn rearm() -> Result<bool, AppError> {
+        SpeakerManager::wake_up().await
+    }
+
+    /// 唤醒小爱（pnshelper src 1 的 event 0），会播放提示音并开始收音
+    pub async fn wake_up() -> Result<bool, AppError> {
+        let res = SpeakerManager::run_shell(WAKE_UP_SCRIPT).await?;
         Ok(res.stdout.contains("\"code\": 0"))
     }
 
+    /// 结束当前的唤醒状态（pnshelper src 3 的 event 7、8），不会唤醒小爱
+    pub async fn exit_wake() -> Result<bool, AppError> {
+        let res = SpeakerManager::run_shell(EXIT_WAKE_SCRIPT).await?;
+        Ok(res.stdout.contains("\"code\": 0"))
+    }
+
+    /// 参数由调用方提供，JSON 和 shell 都需要转义
+    fn play_url_script(url: &str) -> String {
+        let payload = json!({ "url": url, "type": 1 });
+        format!(
+            "ubus call mediaplayer player_play_url {}",
+            shell::quote(&payload.to_string())
+        )
+    }
+
+    fn ask_xiaoai_script(text: &str) -> String {
+        let payload = json!({ "tts": 1, "nlp": 1, "nlp_text": text });
+        format!(
+            "ubus call mibrain ai_service {}",
+            shell::quote(&payload.to_string())
+        )
+    }
+
     async fn run_shell(script: &str) -> Result<CommandResult, AppError> {
+        if LOCAL_SHELL.load(Ordering::Relaxed) {
+            return shell::run_shell(script).await;
+        }
         let res = RPC::instance()
             .call_remote("run_shell", Some(json!(script)), None)
             .await?;
-        Ok(serde_json::from_value::<CommandResult>(res.data.unwrap()).unwrap())
+        Ok(serde_json::from_value::<CommandResult>(res.data)?)
+    }
+}
+
+/// 和说出唤醒词一样唤醒小爱
+const WAKE_UP_SCRIPT: &str = r#"ubus call pnshelper event_notify '{"src":1,"event":0}'"#;
+
+const EXIT_WAKE_SCRIPT: &str = r#"
+    ubus call pnshelper event_notify '{"src":3, "event":7}'
+    sleep 0.1
+    ubus call pnshelper event_notify '{"src":3, "event":8}'
+"#;
+
+fn parse_device_info(stdout: &str) -> DeviceInfo {
+    let mut info = DeviceInfo {
+        version: VERSION.to_string(),
+        ..Default::default()
+    };
+    let mut mem_free = None;
+    for line in stdout.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match key {
+            "model" => info.model = Some(value.to_string()),
+            "serial" => info.serial = Some(value.to_string()),
+            // 形如 option ROM_VERSION '1.2.3'，取最后一段
+            "firmware" => {
+                info.firmware = value
+                    .split(|c: char| c.is_whitespace() || c == '=')
+                    .next_back()
+                    .map(|v| v.trim_matches(|c| c == '\'' || c == '"').to_string())
+                    .filter(|v| !v.is_empty())
+            }
+            "boot" => info.boot_partition = Some(value.to_string()),
+            "uptime" => info.uptime_secs = value.parse::<f64>().ok().map(|v| v as u64),
+            "mem_total" => info.mem_total_kb = value.parse().ok(),
+            "mem_available" => info.mem_available_kb = value.parse().ok(),
+            "mem_free" => mem_free = value.parse().ok(),
+            "load" => {
+                let load = value
+                    .split_whitespace()
+                    .filter_map(|v| v.parse::<f32>().ok())
+                    .collect::<Vec<_>>();
+                info.load = load.try_into().ok();
+            }
+            "data_free" => info.data_free_kb = value.parse().ok(),
+            "ssid" => info.wifi_ssid = Some(value.to_string()),
+            // /proc/net/wireless 中的值带一个小数点，例如 -52.
+            "rssi" => info.wifi_rssi = value.trim_end_matches('.').parse().ok(),
+            "mic" => info.mic_on = Some(value == "on"),
+            _ => {}
+        }
+    }
+    // 老内核没有 MemAvailable
+    if info.mem_available_kb.is_none() {
+        info.mem_available_kb = mem_free;
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTILE: &str = r#"x'; touch /tmp/pwned; echo '"},"type":"$(id)"#;
+
+    /// 用打印 JSON 参数的假 `ubus` 执行脚本，返回收到的参数
+    async fn ubus_payload(script: &str) -> Value {
+        let script = format!("ubus() {{ printf '%s' \"$4\"; }}; {}", script);
+        let res = shell::run_shell(&script).await.unwrap();
+        assert_eq!(res.exit_code, 0, "{}", res.stderr);
+        serde_json::from_str(&res.stdout).unwrap()
+    }
+
+    #[tokio::test]
+    async fn quotes_arbitrary_text_as_one_argument() {
+        let res = shell::run_shell(&format!("printf '%s' {}", shell::quote(HOSTILE)))
+            .await
+            .unwrap();
+        assert_eq!(res.stdout, HOSTILE);
+    }
+
+    #[tokio::test]
+    async fn ubus_payloads_cannot_break_out() {
+        let payload = ubus_payload(&SpeakerManager::play_url_script(HOSTILE)).await;
+        assert_eq!(payload, json!({ "url": HOSTILE, "type": 1 }));
+
+        let payload = ubus_payload(&SpeakerManager::ask_xiaoai_script(HOSTILE)).await;
+        assert_eq!(payload["nlp_text"], HOSTILE);
+        assert_eq!(payload["tts"], 1);
+    }
+
+    #[tokio::test]
+    async fn wake_up_sends_the_wake_event() {
+        let payload = ubus_payload(WAKE_UP_SCRIPT).await;
+        assert_eq!(payload, json!({ "src": 1, "event": 0 }));
     }
 }
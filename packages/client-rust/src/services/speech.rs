@@ -0,0 +1,414 @@
+use regex::{Captures, Regex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{LazyLock, Mutex};
+
+/// 播报前对回复的处理，让 TTS 读出来的是人话
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SpeechConfig {
+    /// 关闭后回复原样播报
+    #[serde(default = "SpeechConfig::default_enabled")]
+    pub enabled: bool,
+    /// 去掉 Markdown 标记、代码块、链接和表情
+    #[serde(rename = "stripMarkdown", default = "SpeechConfig::default_enabled")]
+    pub strip_markdown: bool,
+    /// 把数字、单位、日期和时间转换成中文读法
+    #[serde(default = "SpeechConfig::default_enabled")]
+    pub numbers: bool,
+    /// 一次最多播报的字数，剩下的等用户说“继续”再播，0 表示不限制
+    #[serde(rename = "maxChars", default = "SpeechConfig::default_max_chars")]
+    pub max_chars: usize,
+    /// 截断后追加的提问
+    #[serde(rename = "followUp", default = "SpeechConfig::default_follow_up")]
+    pub follow_up: String,
+    /// 截断后，说这些词会接着播报剩下的内容
+    #[serde(
+        rename = "continueWords",
+        default = "SpeechConfig::default_continue_words"
+    )]
+    pub continue_words: Vec<String>,
+    /// 自定义替换，在转换数字之前进行，例如 {"API": "A P I"}
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub replacements: BTreeMap<String, String>,
+}
+
+impl SpeechConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+
+    fn default_max_chars() -> usize {
+        200
+    }
+
+    fn default_follow_up() -> String {
+        "要我继续说下去吗？".to_string()
+    }
+
+    fn default_continue_words() -> Vec<String> {
+        ["继续", "继续说", "要", "好", "好的", "可以", "说吧"]
+            .map(str::to_string)
+            .to_vec()
+    }
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        Self {
+            enabled: SpeechConfig::default_enabled(),
+            strip_markdown: SpeechConfig::default_enabled(),
+            numbers: SpeechConfig::default_enabled(),
+            max_chars: SpeechConfig::default_max_chars(),
+            follow_up: SpeechConfig::default_follow_up(),
+            continue_words: SpeechConfig::default_continue_words(),
+            replacements: BTreeMap::new(),
+        }
+    }
+}
+
+/// 把回复整理成适合播报的文本，并保存超出长度、还没播报的部分
+pub struct Speech {
+    config: SpeechConfig,
+    pending: Mutex<Option<String>>,
+}
+
+impl Speech {
+    pub fn new(config: SpeechConfig) -> Self {
+        Self {
+            config,
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// 整理一条回复，返回这次要播报的部分
+    pub fn speak(&self, text: &str) -> String {
+        if !self.config.enabled {
+            return text.to_string();
+        }
+        let text = normalize(text, &self.config);
+        self.take_chunk(text)
+    }
+
+    /// 用户要求继续时返回剩下的内容；其他指令会丢弃剩下的内容
+    pub fn resume(&self, instruction: &str) -> Option<String> {
+        let pending = self.pending.lock().unwrap().take()?;
+        let instruction: String = instruction
+            .chars()
+            .filter(|c| !c.is_whitespace() && !is_punctuation(*c))
+            .collect();
+        if !self.config.continue_words.contains(&instruction) {
+            return None;
+        }
+        Some(self.take_chunk(pending))
+    }
+
+    fn take_chunk(&self, text: String) -> String {
+        let (head, rest) = split_at_limit(&text, self.config.max_chars);
+        match rest {
+            Some(rest) => {
+                *self.pending.lock().unwrap() = Some(rest);
+                format!("{}{}", head, self.config.follow_up)
+            }
+            None => {
+                *self.pending.lock().unwrap() = None;
+                head
+            }
+        }
+    }
+}
+
+/// 按配置整理文本，不截断
+pub fn normalize(text: &str, config: &SpeechConfig) -> String {
+    let mut text = text.to_string();
+    if config.strip_markdown {
+        text = strip_markdown(&text);
+    }
+    for (from, to) in &config.replacements {
+        text = text.replace(from.as_str(), to);
+    }
+    if config.numbers {
+        text = speak_numbers(&text);
+    }
+    text
+}
+
+static CODE_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)```.*?(```|$)").unwrap());
+static IMAGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"!\[([^\]]*)\]\([^)]*\)").unwrap());
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\]]+)\]\([^)]*\)").unwrap());
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(https?://|www\.)[A-Za-z0-9\-._~:/?#\[\]@!$&'*+,;=%]+").unwrap());
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"</?[A-Za-z][^>]*>").unwrap());
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`]*)`").unwrap());
+static EMPHASIS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\*+|__|~~").unwrap());
+static LINE_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(#{1,6}\s+|>\s?|[-*+]\s+|\d+[.)]\s+)+").unwrap());
+static RULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\|?[\s:|]*([-*_=]\s*){3,}[\s:|\-]*$").unwrap());
+
+/// 去掉 Markdown 标记，按行拼成连续的句子
+pub fn strip_markdown(text: &str) -> String {
+    let text = CODE_BLOCK.replace_all(text, "");
+    let text = IMAGE.replace_all(&text, "$1");
+    let text = LINK.replace_all(&text, "$1");
+    let text = URL.replace_all(&text, "");
+    let text = HTML_TAG.replace_all(&text, "");
+    let text = INLINE_CODE.replace_all(&text, "$1");
+
+    let mut out = String::new();
+    for line in text.lines() {
+        if RULE.is_match(line) {
+            continue;
+        }
+        let line = LINE_MARKER.replace(line, "");
+        let line = EMPHASIS.replace_all(&line, "");
+        // 表格的每一行读成一句，单元格之间停顿
+        let line = line
+            .split('|')
+            .map(str::trim)
+            .filter(|cell| !cell.is_empty())
+            .collect::<Vec<_>>()
+            .join("，");
+        let line: String = line.chars().filter(|c| !is_emoji(*c)).collect();
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        let line = line.trim_matches(|c: char| c == '，' || c.is_whitespace());
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(last) = out.chars().last() {
+            if !is_punctuation(last) {
+                out.push('，');
+            }
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0F | 0x200D | 0x20E3)
+}
+
+fn is_punctuation(c: char) -> bool {
+    "。！？；，、：….!?;:,".contains(c)
+}
+
+const DIGITS: [char; 10] = ['零', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// 逐位读数字，例如 110 读作“一一零”
+pub fn digits(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_digit(10).map_or(c, |d| DIGITS[d as usize]))
+        .collect()
+}
+
+/// 整数的中文读法，例如 10010 读作“一万零一十”
+pub fn integer(n: u64) -> String {
+    if n == 0 {
+        return "零".to_string();
+    }
+    if n >= 10_000_000_000_000_000 {
+        return digits(&n.to_string());
+    }
+    const GROUPS: [&str; 4] = ["", "万", "亿", "万亿"];
+    let mut out = String::new();
+    let mut zero = false;
+    let groups: Vec<u64> = (0..4).map(|i| n / 10_000u64.pow(i) % 10_000).collect();
+    for (i, &group) in groups.iter().enumerate().rev() {
+        if group == 0 {
+            zero = !out.is_empty();
+            continue;
+        }
+        if !out.is_empty() && (zero || group < 1000) {
+            out.push('零');
+        }
+        out.push_str(&thousands(group));
+        out.push_str(GROUPS[i]);
+        zero = false;
+    }
+    // 十几不读作“一十几”
+    match out.strip_prefix("一十") {
+        Some(rest) => format!("十{}", rest),
+        None => out,
+    }
+}
+
+/// 0 到 9999
+fn thousands(n: u64) -> String {
+    const UNITS: [&str; 4] = ["千", "百", "十", ""];
+    let mut out = String::new();
+    let mut zero = false;
+    for (i, unit) in UNITS.iter().enumerate() {
+        let digit = n / 10u64.pow(3 - i as u32) % 10;
+        if digit == 0 {
+            zero = !out.is_empty();
+            continue;
+        }
+        if zero {
+            out.push('零');
+            zero = false;
+        }
+        out.push(DIGITS[digit as usize]);
+        out.push_str(unit);
+    }
+    out
+}
+
+/// 数字的读法，小数点后逐位读；有前导零或太长的数字（电话号码、编号）逐位读
+pub fn number(text: &str) -> String {
+    let (whole, fraction) = match text.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (text, None),
+    };
+    let whole = match whole.parse::<u64>() {
+        Ok(n) if whole.len() < 11 && !(whole.len() > 1 && whole.starts_with('0')) => integer(n),
+        _ => digits(whole),
+    };
+    match fraction {
+        Some(fraction) => format!("{}点{}", whole, digits(fraction)),
+        None => whole,
+    }
+}
+
+/// 单位的读法，只在紧跟数字时替换
+const UNITS: [(&str, &str); 24] = [
+    ("km/h", "公里每小时"),
+    ("m/s", "米每秒"),
+    ("km", "公里"),
+    ("cm", "厘米"),
+    ("mm", "毫米"),
+    ("m", "米"),
+    ("kg", "公斤"),
+    ("mg", "毫克"),
+    ("g", "克"),
+    ("ml", "毫升"),
+    ("mL", "毫升"),
+    ("L", "升"),
+    ("°C", "摄氏度"),
+    ("℃", "摄氏度"),
+    ("°F", "华氏度"),
+    ("°", "度"),
+    ("h", "小时"),
+    ("min", "分钟"),
+    ("s", "秒"),
+    ("ms", "毫秒"),
+    ("kWh", "千瓦时"),
+    ("kW", "千瓦"),
+    ("W", "瓦"),
+    ("mAh", "毫安时"),
+];
+
+static THOUSANDS_SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d{1,3}(,\d{3})+").unwrap());
+static DATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d{4})[-/.](\d{1,2})[-/.](\d{1,2})").unwrap());
+static YEAR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d{4})年").unwrap());
+static TIME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d{1,2}):(\d{2})(?::(\d{2}))?").unwrap());
+static PERCENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+(?:\.\d+)?)\s?[%％]").unwrap());
+static CURRENCY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([$＄¥￥€])\s?(\d+(?:\.\d+)?)").unwrap());
+static RANGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d+(?:\.\d+)?)\s?([-~～])\s?(\d+(?:\.\d+)?)").unwrap());
+static QUANTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d+(?:\.\d+)?)\s?([A-Za-z°℃/]+)").unwrap());
+static NEGATIVE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|[^0-9A-Za-z.])[-－−](\d)").unwrap());
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+(?:\.\d+)?").unwrap());
+
+/// 范围后面常见的量词，用来区分“3-5天”和“5-3”
+const RANGE_UNITS: &str = "个天年月日周岁次人件元块倍度分秒斤层号";
+
+/// 是否读作范围：两边都是不超过四位的数字，用连字符时后面还要跟着单位，
+/// 避免把电话号码和算式读成“到”
+fn is_range(c: &Captures, rest: &str) -> bool {
+    let short = |n: &str| n.split('.').next().map_or(0, str::len) <= 4;
+    if !short(&c[1]) || !short(&c[3]) {
+        return false;
+    }
+    if &c[2] != "-" {
+        return true;
+    }
+    let rest = rest.trim_start();
+    rest.starts_with(|ch| RANGE_UNITS.contains(ch))
+        || UNITS.iter().any(|(unit, _)| rest.starts_with(unit))
+}
+
+/// 把日期、时间、百分比、金额、单位和数字换成中文读法
+pub fn speak_numbers(text: &str) -> String {
+    let text = THOUSANDS_SEPARATOR.replace_all(text, |c: &Captures| c[0].replace(',', ""));
+    let text = DATE.replace_all(&text, |c: &Captures| {
+        let (month, day) = (c[2].parse().unwrap_or(0), c[3].parse().unwrap_or(0));
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return c[0].to_string();
+        }
+        format!("{}年{}月{}日", digits(&c[1]), integer(month), integer(day))
+    });
+    let text = YEAR.replace_all(&text, |c: &Captures| format!("{}年", digits(&c[1])));
+    let text = TIME.replace_all(&text, |c: &Captures| {
+        let hour: u64 = c[1].parse().unwrap_or(99);
+        let minute: u64 = c[2].parse().unwrap_or(99);
+        let second: Option<u64> = c.get(3).and_then(|s| s.as_str().parse().ok());
+        if hour > 23 || minute > 59 || second.is_some_and(|s| s > 59) {
+            return c[0].to_string();
+        }
+        let mut out = format!("{}点", integer(hour));
+        if minute > 0 || second.is_some_and(|s| s > 0) {
+            let zero = if minute < 10 { "零" } else { "" };
+            out.push_str(&format!("{}{}分", zero, integer(minute)));
+        }
+        if let Some(second) = second.filter(|s| *s > 0) {
+            out.push_str(&format!("{}秒", integer(second)));
+        }
+        out
+    });
+    let text = PERCENT.replace_all(&text, |c: &Captures| format!("百分之{}", number(&c[1])));
+    let text = CURRENCY.replace_all(&text, |c: &Captures| {
+        let unit = match &c[1] {
+            "$" | "＄" => "美元",
+            "€" => "欧元",
+            _ => "元",
+        };
+        format!("{}{}", number(&c[2]), unit)
+    });
+    let text = RANGE.replace_all(&text, |c: &Captures| {
+        let rest = &text[c.get(0).map_or(0, |m| m.end())..];
+        if is_range(c, rest) {
+            format!("{}到{}", &c[1], &c[3])
+        } else {
+            c[0].to_string()
+        }
+    });
+    let text = NEGATIVE.replace_all(&text, "${1}负${2}");
+    let text = QUANTITY.replace_all(&text, |c: &Captures| {
+        match UNITS.iter().find(|(unit, _)| *unit == &c[2]) {
+            Some((_, spoken)) => format!("{}{}", number(&c[1]), spoken),
+            None => c[0].to_string(),
+        }
+    });
+    NUMBER
+        .replace_all(&text, |c: &Captures| number(&c[0]))
+        .into_owned()
+}
+
+/// 在不超过 limit 个字的最后一个句末处断开，找不到句末时退而在逗号处断开
+fn split_at_limit(text: &str, limit: usize) -> (String, Option<String>) {
+    let chars: Vec<char> = text.chars().collect();
+    if limit == 0 || chars.len() <= limit {
+        return (text.to_string(), None);
+    }
+    let window = &chars[..limit];
+    let last = |marks: &str| window.iter().rposition(|c| marks.contains(*c));
+    let cut = last("。！？!?；;\n")
+        .filter(|&i| i >= limit / 2)
+        .or_else(|| last("，,、：:").filter(|&i| i >= limit / 2))
+        .map_or(limit, |i| i + 1);
+    let head: String = chars[..cut].iter().collect();
+    let rest: String = chars[cut..].iter().collect();
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return (head, None);
+    }
+    (head, Some(rest.to_string()))
+}
@@ -0,0 +1,129 @@
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::RwLock;
+use warp::http::StatusCode;
+use warp::Filter;
+
+use crate::base::AppError;
+use crate::utils::task::TaskManager;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StatusConfig {
+    /// 监听地址，默认只对本机开放
+    #[serde(default = "StatusConfig::default_listen")]
+    pub listen: String,
+}
+
+impl StatusConfig {
+    fn default_listen() -> String {
+        "127.0.0.1:4398".to_string()
+    }
+
+    pub fn addr(&self) -> Result<SocketAddr, AppError> {
+        self.listen
+            .parse()
+            .map_err(|e| format!("invalid status listen address {}: {}", self.listen, e).into())
+    }
+}
+
+impl Default for StatusConfig {
+    fn default() -> Self {
+        Self {
+            listen: StatusConfig::default_listen(),
+        }
+    }
+}
+
+type StatusProvider = Arc<dyn Fn() -> BoxFuture<'static, Value> + Send + Sync>;
+
+/// 本机状态接口，各个模块注册自己的状态，通过 HTTP 查询
+pub struct StatusServer {
+    providers: Arc<RwLock<BTreeMap<String, StatusProvider>>>,
+}
+
+static INSTANCE: LazyLock<StatusServer> = LazyLock::new(StatusServer::new);
+
+impl StatusServer {
+    fn new() -> Self {
+        Self {
+            providers: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn instance() -> &'static Self {
+        &INSTANCE
+    }
+
+    pub async fn register<F, Fut>(&self, name: &str, provider: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Value> + Send + 'static,
+    {
+        self.providers
+            .write()
+            .await
+            .insert(name.to_string(), Arc::new(move || Box::pin(provider())));
+    }
+
+    pub async fn unregister(&self, name: &str) {
+        self.providers.write().await.remove(name);
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Value> {
+        let provider = self.providers.read().await.get(name).cloned()?;
+        Some(provider().await)
+    }
+
+    pub async fn snapshot(&self) -> Value {
+        let providers = self
+            .providers
+            .read()
+            .await
+            .iter()
+            .map(|(name, provider)| (name.clone(), provider.clone()))
+            .collect::<Vec<_>>();
+        let mut snapshot = Map::new();
+        for (name, provider) in providers {
+            snapshot.insert(name, provider().await);
+        }
+        Value::Object(snapshot)
+    }
+
+    /// 启动 HTTP 服务：GET /status 与 GET /status/{name}
+    pub async fn serve(&self, addr: SocketAddr) {
+        let all = warp::path!("status").and(warp::get()).and_then(|| async {
+            let snapshot = StatusServer::instance().snapshot().await;
+            Ok::<_, Infallible>(warp::reply::json(&snapshot))
+        });
+
+        let one =
+            warp::path!("status" / String)
+                .and(warp::get())
+                .and_then(|name: String| async move {
+                    let reply = match StatusServer::instance().get(&name).await {
+                        Some(status) => {
+                            warp::reply::with_status(warp::reply::json(&status), StatusCode::OK)
+                        }
+                        None => warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"error": "unknown status"})),
+                            StatusCode::NOT_FOUND,
+                        ),
+                    };
+                    Ok::<_, Infallible>(reply)
+                });
+
+        let server = tokio::spawn(warp::serve(all.or(one)).run(addr));
+        TaskManager::instance().add("StatusServer", server).await;
+    }
+
+    pub async fn stop(&self) {
+        TaskManager::instance().dispose("StatusServer").await;
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json::{json, Value};
+
+use crate::base::AppError;
+use crate::services::speaker::SpeakerManager;
+
+/// 提供给大模型调用的音箱工具，格式兼容 OpenAI function calling
+pub struct SpeakerTools;
+
+impl SpeakerTools {
+    /// chat/completions 请求中的 tools 字段
+    pub fn definitions() -> Value {
+        json!([
+            {
+                "type": "function",
+                "function": {
+                    "name": "get_volume",
+                    "description": "Get the speaker's media volume (0-100), mute state and assistant voice volume",
+                    "parameters": { "type": "object", "properties": {} }
+                }
+            },
+            {
+                "type": "function",
+                "function": {
+                    "name": "set_volume",
+                    "description": "Set the speaker's volume. target 'media' changes music and other playback, 'assistant' only changes the assistant's own voice",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "volume": { "type": "integer", "minimum": 0, "maximum": 100 },
+                            "target": { "type": "string", "enum": ["media", "assistant"] }
+                        },
+                        "required": ["volume"]
+                    }
+                }
+            },
+            {
+                "type": "function",
+                "function": {
+                    "name": "step_volume",
+                    "description": "Turn the media volume up (positive delta) or down (negative delta)",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {
+                            "delta": { "type": "integer", "minimum": -100, "maximum": 100 }
+                        },
+                        "required": ["delta"]
+                    }
+                }
+            },
+            {
+                "type": "function",
+                "function": {
+                    "name": "mute",
+                    "description": "Mute the speaker output",
+                    "parameters": { "type": "object", "properties": {} }
+                }
+            },
+            {
+                "type": "function",
+                "function": {
+                    "name": "unmute",
+                    "description": "Unmute the speaker output",
+                    "parameters": { "type": "object", "properties": {} }
+                }
+            }
+        ])
+    }
+
+    /// 执行一次工具调用，arguments 是模型返回的 JSON 字符串
+    pub async fn call(name: &str, arguments: &str) -> Result<Value, AppError> {
+        let args = if arguments.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str::<Value>(arguments)?
+        };
+        match name {
+            "get_volume" => Ok(json!(SpeakerManager::volume_state().await?)),
+            "set_volume" => {
+                let volume = args
+                    .get("volume")
+                    .and_then(Value::as_u64)
+                    .ok_or("missing volume")?
+                    .min(100) as u8;
+                match args.get("target").and_then(Value::as_str) {
+                    Some("assistant") => {
+                        SpeakerManager::set_assistant_volume(Some(volume)).await;
+                        Ok(json!({ "ok": true, "assistant_volume": volume }))
+                    }
+                    _ => {
+                        let ok = SpeakerManager::set_volume(volume).await?;
+                        Ok(json!({ "ok": ok, "volume": volume }))
+                    }
+                }
+            }
+            "step_volume" => {
+                let delta = args
+                    .get("delta")
+                    .and_then(Value::as_i64)
+                    .ok_or("missing delta")?
+                    .clamp(-100, 100);
+                let volume = SpeakerManager::step_volume(delta as i32).await?;
+                Ok(json!({ "ok": true, "volume": volume }))
+            }
+            "mute" => Ok(json!({ "ok": SpeakerManager::mute().await? })),
+            "unmute" => Ok(json!({ "ok": SpeakerManager::unmute().await? })),
+            _ => Err(format!("unknown tool: {}", name).into()),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::{Client, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::base::{AppError, VERSION};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UpdateConfig {
+    /// 发布清单的地址，不填时使用代理服务器的 /update/manifest.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 发布签名公钥，hex 编码的 ed25519 公钥
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+    /// 检查更新的间隔秒数
+    #[serde(
+        rename = "checkInterval",
+        default = "UpdateConfig::default_check_interval"
+    )]
+    pub check_interval: u64,
+}
+
+impl UpdateConfig {
+    fn default_check_interval() -> u64 {
+        6 * 60 * 60
+    }
+}
+
+/// 发布清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    /// 二进制的下载地址，可以是相对清单的路径
+    pub url: String,
+    /// 二进制的 SHA-256，hex 编码
+    pub sha256: String,
+    /// 对 signed_message(version, sha256) 的 ed25519 签名，hex 编码
+    pub signature: String,
+}
+
+/// 下载、校验并替换 client 二进制
+pub struct Updater {
+    manifest_url: Url,
+    public_key: VerifyingKey,
+    binary: PathBuf,
+    client: Client,
+    /// 不要求 TLS 的来源，只用于代理服务器
+    trusted_origin: Option<String>,
+}
+
+/// 发布签名覆盖的内容：版本号和二进制摘要，防止旧版本被改成新版本号
+pub fn signed_message(version: &str, sha256: &[u8]) -> Vec<u8> {
+    format!("{}\n{}", version, hex::encode(sha256)).into_bytes()
+}
+
+impl Updater {
+    /// manifest_url 必须是 https，本机地址除外
+    pub fn new(manifest_url: &str, public_key: &str) -> Result<Self, AppError> {
+        Updater::build(manifest_url, public_key, false)
+    }
+
+    fn build(manifest_url: &str, public_key: &str, trusted: bool) -> Result<Self, AppError> {
+        let manifest_url = Url::parse(manifest_url)?;
+        let trusted_origin = trusted.then(|| manifest_url.origin().ascii_serialization());
+        require_tls(&manifest_url, trusted_origin.as_deref())?;
+        let key = decode_hex::<32>(public_key, "public key")?;
+        Ok(Self {
+            manifest_url,
+            public_key: VerifyingKey::from_bytes(&key)?,
+            binary: std::env::current_exe()?,
+            client: Client::builder()
+                .timeout(Duration::from_secs(300))
+                .build()?,
+            trusted_origin,
+        })
+    }
+
+    /// 没有配置 url 时使用代理服务器上的清单，代理服务器可以不用 https
+    pub fn from_config(
+        config: &UpdateConfig,
+        proxy_base_url: Option<&str>,
+    ) -> Result<Self, AppError> {
+        match (&config.url, proxy_base_url) {
+            (Some(url), _) => Updater::new(url, &config.public_key),
+            (None, Some(base)) => {
+                let url = format!("{}/update/manifest.json", base.trim_end_matches('/'));
+                Updater::build(&url, &config.public_key, true)
+            }
+            (None, None) => Err("update.url is required outside proxy mode".into()),
+        }
+    }
+
+    /// 替换指定路径的二进制，默认是当前可执行文件
+    pub fn with_binary(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.binary = binary.into();
+        self
+    }
+
+    pub fn binary(&self) -> &Path {
+        &self.binary
+    }
+
+    /// 旧版本的备份路径
+    pub fn previous_binary(&self) -> PathBuf {
+        sibling(&self.binary, "prev")
+    }
+
+    /// 有更新的版本时返回发布清单
+    pub async fn check(&self) -> Result<Option<Manifest>, AppError> {
+        let response = self.client.get(self.manifest_url.clone()).send().await?;
+        if !response.status().is_success() {
+            return Err(format!("manifest request failed: HTTP {}", response.status()).into());
+        }
+        let manifest: Manifest = response.json().await?;
+        if compare_versions(&manifest.version, VERSION) == Ordering::Greater {
+            Ok(Some(manifest))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 下载二进制并校验摘要与签名
+    pub async fn download(&self, manifest: &Manifest) -> Result<Vec<u8>, AppError> {
+        let url = self.manifest_url.join(&manifest.url)?;
+        require_tls(&url, self.trusted_origin.as_deref())?;
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(format!("download failed: HTTP {}", response.status()).into());
+        }
+        let bytes = response.bytes().await?.to_vec();
+        self.verify(manifest, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// 签名同时校验版本号和摘要
+    pub fn verify(&self, manifest: &Manifest, bytes: &[u8]) -> Result<(), AppError> {
+        let digest = Sha256::digest(bytes);
+        let expected = decode_hex::<32>(&manifest.sha256, "sha256")?;
+        if digest.as_slice() != expected {
+            return Err("sha256 mismatch".into());
+        }
+        let signature = Signature::from_bytes(&decode_hex::<64>(&manifest.signature, "signature")?);
+        self.public_key
+            .verify_strict(&signed_message(&manifest.version, &digest), &signature)
+            .map_err(|_| "invalid signature")?;
+        Ok(())
+    }
+
+    /// 原子替换二进制，旧版本保留为 .prev
+    pub fn install(&self, bytes: &[u8]) -> Result<(), AppError> {
+        let staged = sibling(&self.binary, "new");
+        std::fs::write(&staged, bytes)?;
+        {
+            let file = std::fs::File::open(&staged)?;
+            file.sync_all()?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+        }
+
+        if self.binary.exists() {
+            let previous = self.previous_binary();
+            let _ = std::fs::remove_file(&previous);
+            // 硬链接失败（比如跨文件系统）时退回复制
+            if std::fs::hard_link(&self.binary, &previous).is_err() {
+                std::fs::copy(&self.binary, &previous)?;
+            }
+        }
+        // rename 是原子的，任何时刻 binary 都是一个完整的文件
+        std::fs::rename(&staged, &self.binary)?;
+        Ok(())
+    }
+
+    /// 恢复 .prev 中的旧版本
+    pub fn rollback(&self) -> Result<(), AppError> {
+        let previous = self.previous_binary();
+        if !previous.exists() {
+            return Err("no previous binary to roll back to".into());
+        }
+        std::fs::rename(&previous, &self.binary)?;
+        Ok(())
+    }
+
+    /// 检查、下载并安装，返回安装的版本
+    pub async fn update(&self) -> Result<Option<String>, AppError> {
+        let Some(manifest) = self.check().await? else {
+            return Ok(None);
+        };
+        let bytes = self.download(&manifest).await?;
+        self.install(&bytes)?;
+        Ok(Some(manifest.version))
+    }
+
+    /// 以相同的参数重新执行新的二进制，成功时不会返回
+    #[cfg(unix)]
+    pub fn reexec(&self) -> AppError {
+        use std::os::unix::process::CommandExt;
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        std::process::Command::new(&self.binary)
+            .args(args)
+            .exec()
+            .into()
+    }
+
+    /// 只支持 unix，其他平台需要手动重启
+    #[cfg(not(unix))]
+    pub fn reexec(&self) -> AppError {
+        format!(
+            "restart {} manually, re-exec is only supported on unix",
+            self.binary.display()
+        )
+        .into()
+    }
+}
+
+/// 只允许通过 TLS 下载，本机地址和信任的来源除外
+fn require_tls(url: &Url, trusted: Option<&str>) -> Result<(), AppError> {
+    let loopback = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    let trusted = trusted.is_some_and(|origin| url.origin().ascii_serialization() == origin);
+    if url.scheme() == "https" || (url.scheme() == "http" && (loopback || trusted)) {
+        Ok(())
+    } else {
+        Err(format!("refusing to download over insecure URL: {}", url).into())
+    }
+}
+
+fn decode_hex<const N: usize>(value: &str, name: &str) -> Result<[u8; N], AppError> {
+    let bytes = hex::decode(value.trim()).map_err(|e| format!("invalid {}: {}", name, e))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("invalid {}: expected {} bytes", name, N).into())
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// 按点分的数字比较版本号，非数字部分按 0 处理
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| {
+        v.trim_start_matches('v')
+            .split('.')
+            .map(|part| {
+                part.chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect::<String>()
+                    .parse::<u64>()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (parse(a), parse(b));
+    for i in 0..a.len().max(b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
@@ -1,19 +1,163 @@
-use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    collections::HashMap,
     future::Future,
-    sync::{Arc, LazyLock},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, RwLock,
+    },
 };
-use tokio::sync::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 use crate::{base::AppError, services::connect::data::Event};
 
 use super::task::TaskManager;
 
-type EventCallback = Arc<dyn Fn(Event) -> BoxFuture<'static, Result<(), AppError>> + Send + Sync>;
+/// 订阅的主题，`*` 匹配所有事件，`kws.*` 匹配 `kws` 及其下的所有子主题
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pattern {
+    All,
+    Exact(String),
+    Prefix(String),
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Self {
+        if pattern == "*" {
+            Pattern::All
+        } else if let Some(prefix) = pattern.strip_suffix(".*") {
+            Pattern::Prefix(prefix.to_string())
+        } else {
+            Pattern::Exact(pattern.to_string())
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::All => true,
+            Pattern::Exact(exact) => exact == name,
+            Pattern::Prefix(prefix) => name
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.')),
+        }
+    }
+}
+
+/// 订阅者处理不过来、队列溢出时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// 丢弃最旧的事件，继续处理
+    #[default]
+    Skip,
+    /// 结束订阅
+    Close,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscribeOptions {
+    /// 每个订阅者的队列长度
+    pub capacity: usize,
+    pub lag: LagPolicy,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            lag: LagPolicy::Skip,
+        }
+    }
+}
+
+/// 带类型的主题，负载通过 serde 转换
+pub struct Topic<T> {
+    name: &'static str,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> Topic<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _payload: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+struct Subscriber {
+    id: u64,
+    pattern: String,
+    matcher: Pattern,
+    sender: broadcast::Sender<Event>,
+}
+
+/// 订阅句柄，被 drop 时自动取消订阅
+#[must_use = "the subscription is cancelled as soon as the handle is dropped"]
+pub struct Subscription {
+    id: u64,
+    pattern: String,
+    detached: bool,
+}
+
+impl Subscription {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 保持订阅直到 `EventBus::unsubscribe` 被调用
+    pub fn detach(mut self) {
+        self.detached = true;
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if !self.detached {
+            EventBus::instance().remove(self.id);
+        }
+    }
+}
+
+/// 订阅者的事件队列
+pub struct EventReceiver {
+    receiver: broadcast::Receiver<Event>,
+    lag: LagPolicy,
+    subscription: Subscription,
+}
+
+impl EventReceiver {
+    /// 下一个事件，订阅结束时返回 None
+    pub async fn recv(&mut self) -> Option<Event> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Closed) => return None,
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!(
+                        "⚠️  [EventBus] subscriber {} lagged, {} events dropped",
+                        self.subscription.pattern, skipped
+                    );
+                    if self.lag == LagPolicy::Close {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
 
+    pub fn subscription(&self) -> &Subscription {
+        &self.subscription
+    }
+}
+
+/// 进程内事件总线：每个订阅者有独立的有界队列，发布方从不等待订阅者
 pub struct EventBus {
-    subscribers: Arc<Mutex<HashMap<String, Vec<EventCallback>>>>,
+    subscribers: RwLock<Vec<Subscriber>>,
+    next_id: AtomicU64,
 }
 
 static INSTANCE: LazyLock<EventBus> = LazyLock::new(EventBus::new);
@@ -21,7 +165,8 @@ static INSTANCE: LazyLock<EventBus> = LazyLock::new(EventBus::new);
 impl EventBus {
     fn new() -> Self {
         EventBus {
-            subscribers: Arc::new(Mutex::new(HashMap::new())),
+            subscribers: RwLock::new(Vec::new()),
+            next_id: AtomicU64::new(1),
         }
     }
 
@@ -29,53 +174,206 @@ impl EventBus {
         &INSTANCE
     }
 
-    pub async fn subscribe<F, Fut>(&self, event: &str, callback: F)
+    /// 订阅事件队列，自行调用 `recv` 消费
+    pub fn receiver(&self, pattern: &str, options: SubscribeOptions) -> EventReceiver {
+        let (sender, receiver) = broadcast::channel(options.capacity.max(1));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.write().unwrap().push(Subscriber {
+            id,
+            pattern: pattern.to_string(),
+            matcher: Pattern::parse(pattern),
+            sender,
+        });
+        EventReceiver {
+            receiver,
+            lag: options.lag,
+            subscription: Subscription {
+                id,
+                pattern: pattern.to_string(),
+                detached: false,
+            },
+        }
+    }
+
+    pub async fn subscribe<F, Fut>(&self, pattern: &str, callback: F) -> Subscription
     where
         F: Fn(Event) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = Result<(), AppError>> + Send + 'static,
     {
-        let mut subscribers = self.subscribers.lock().await;
-        subscribers
-            .entry(event.to_string())
-            .or_insert_with(Vec::new)
-            .push(Arc::new(move |event| Box::pin(callback(event))));
+        self.subscribe_with(pattern, SubscribeOptions::default(), callback)
+            .await
     }
 
-    pub async fn unsubscribe(&self, event: &str) {
-        self.subscribers.lock().await.remove(event);
-        let tag = format!("EventBus-{}", event);
-        TaskManager::instance().dispose(&tag).await;
+    /// 回调在独立的任务中按顺序执行，处理慢只会让自己的队列溢出
+    pub async fn subscribe_with<F, Fut>(
+        &self,
+        pattern: &str,
+        options: SubscribeOptions,
+        callback: F,
+    ) -> Subscription
+    where
+        F: Fn(Event) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        let EventReceiver {
+            receiver,
+            lag,
+            subscription,
+        } = self.receiver(pattern, options);
+        let mut events = EventReceiver {
+            receiver,
+            lag,
+            // 句柄由调用方持有，这里只负责接收
+            subscription: Subscription {
+                id: subscription.id,
+                pattern: subscription.pattern.clone(),
+                detached: true,
+            },
+        };
+
+        let tag = format!("EventBus-{}", pattern);
+        TaskManager::instance()
+            .spawn(&tag, move |token| async move {
+                loop {
+                    let event = tokio::select! {
+                        _ = token.cancelled() => break,
+                        event = events.recv() => event,
+                    };
+                    let Some(event) = event else {
+                        break;
+                    };
+                    let name = event.name.clone();
+                    if let Err(e) = callback(event).await {
+                        eprintln!("⚠️  [EventBus] {} handler failed: {}", name, e);
+                    }
+                }
+            })
+            .await;
+        subscription
+    }
+
+    /// 订阅带类型的主题，无法解析的负载会被跳过
+    pub async fn subscribe_typed<T, F, Fut>(&self, topic: &Topic<T>, callback: F) -> Subscription
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        let callback = Arc::new(callback);
+        self.subscribe(topic.name, move |event| {
+            let callback = Arc::clone(&callback);
+            async move {
+                let payload = serde_json::from_value::<T>(event.data)
+                    .map_err(|e| format!("invalid {} payload: {}", event.name, e))?;
+                callback(payload).await
+            }
+        })
+        .await
+    }
+
+    /// 取消某个主题下的所有订阅
+    pub async fn unsubscribe(&self, pattern: &str) {
+        self.subscribers
+            .write()
+            .unwrap()
+            .retain(|subscriber| subscriber.pattern != pattern);
     }
 
+    /// 把事件放进所有匹配订阅者的队列，不等待订阅者处理
     pub async fn publish(&self, event: Event) {
-        let Some(callbacks) = self.get_callbacks(&event.event).await else {
-            return;
-        };
-        for callback in callbacks {
-            let event = event.clone();
-            let _ = callback(event).await;
+        let subscribers = self.subscribers.read().unwrap();
+        for subscriber in subscribers.iter() {
+            if subscriber.matcher.matches(&event.name) {
+                let _ = subscriber.sender.send(event.clone());
+            }
         }
     }
 
-    pub async fn publish_async(&self, event: Event) {
-        let Some(callbacks) = self.get_callbacks(&event.event).await else {
-            return;
-        };
-        let tag = format!("EventBus-{}", event.event);
-        for callback in callbacks {
-            let event = event.clone();
-            let task = tokio::spawn(async move {
-                let _ = callback(event).await;
-            });
-            TaskManager::instance().add(&tag, task).await;
+    pub async fn publish_typed<T: Serialize>(&self, topic: &Topic<T>, payload: &T) {
+        match serde_json::to_value(payload) {
+            Ok(data) => self.publish(Event::new(topic.name, data)).await,
+            Err(e) => eprintln!("⚠️  [EventBus] failed to encode {}: {}", topic.name, e),
         }
     }
 
-    async fn get_callbacks(&self, event: &str) -> Option<Vec<EventCallback>> {
-        let subscribers = self.subscribers.lock().await;
-        match subscribers.get(event) {
-            Some(callbacks) => Some(callbacks.clone()),
-            None => None,
+    fn remove(&self, id: u64) {
+        if let Ok(mut subscribers) = self.subscribers.write() {
+            subscribers.retain(|subscriber| subscriber.id != id);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn subscribed(pattern: &str) -> usize {
+        let subscribers = EventBus::instance().subscribers.read().unwrap();
+        subscribers.iter().filter(|s| s.pattern == pattern).count()
+    }
+
+    #[test]
+    fn wildcards_match_whole_segments() {
+        let kws = Pattern::parse("kws.*");
+        assert!(kws.matches("kws"));
+        assert!(kws.matches("kws.keyword"));
+        assert!(kws.matches("kws.keyword.detail"));
+        assert!(!kws.matches("kwsx.keyword"));
+        assert!(!kws.matches("instruction"));
+
+        assert!(Pattern::parse("*").matches("anything"));
+        assert!(Pattern::parse("kws.started").matches("kws.started"));
+        assert!(!Pattern::parse("kws.started").matches("kws.started.late"));
+    }
+
+    #[tokio::test]
+    async fn events_reach_only_matching_subscribers() {
+        let bus = EventBus::instance();
+        let mut wildcard = bus.receiver("test-route.*", SubscribeOptions::default());
+        let mut exact = bus.receiver("test-route.b", SubscribeOptions::default());
+
+        bus.publish(Event::new("test-route.a", json!(1))).await;
+        bus.publish(Event::new("test-route.b", json!(2))).await;
+
+        assert_eq!(wildcard.recv().await.unwrap().data, json!(1));
+        assert_eq!(wildcard.recv().await.unwrap().data, json!(2));
+        assert_eq!(exact.recv().await.unwrap().data, json!(2));
+    }
+
+    #[tokio::test]
+    async fn lagging_subscribers_skip_or_close() {
+        let bus = EventBus::instance();
+        let options = |lag| SubscribeOptions { capacity: 2, lag };
+        let mut skipping = bus.receiver("test-lag", options(LagPolicy::Skip));
+        let mut closing = bus.receiver("test-lag", options(LagPolicy::Close));
+
+        for i in 0..4 {
+            bus.publish(Event::new("test-lag", json!(i))).await;
+        }
+
+        // 丢弃最旧的事件，最新的事件照常送达
+        assert_eq!(skipping.recv().await.unwrap().data, json!(2));
+        assert_eq!(skipping.recv().await.unwrap().data, json!(3));
+        assert!(closing.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_handle_unsubscribes() {
+        let bus = EventBus::instance();
+        let receiver = bus.receiver("test-drop", SubscribeOptions::default());
+        let subscription = bus.subscribe("test-drop", |_| async { Ok(()) }).await;
+        assert_eq!(subscribed("test-drop"), 2);
+
+        drop(receiver);
+        drop(subscription);
+        assert_eq!(subscribed("test-drop"), 0);
+
+        let detached = bus.receiver("test-detach", SubscribeOptions::default());
+        let EventReceiver { subscription, .. } = detached;
+        subscription.detach();
+        assert_eq!(subscribed("test-detach"), 1);
+        bus.unsubscribe("test-detach").await;
+        assert_eq!(subscribed("test-detach"), 0);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod event;
+pub mod rand;
 pub mod shell;
 pub mod task;
-pub mod rand;
\ No newline at end of file
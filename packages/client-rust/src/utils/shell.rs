@@ -27,3 +27,8 @@ pub async fn run_shell(script: &str) -> Result<CommandResult, AppError> {
         exit_code,
     })
 }
+
+/// 把任意字符串包成一个 shell 单引号参数
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
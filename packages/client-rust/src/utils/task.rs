@@ -4,11 +4,46 @@ use std::{
     sync::{Arc, LazyLock},
 };
 
-use tokio::{sync::Mutex, task::JoinHandle};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, Mutex},
+    task::{AbortHandle, JoinError, JoinHandle},
+    time::{sleep, Duration, Instant},
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::base::AppError;
+
+struct TaskEntry {
+    handle: JoinHandle<()>,
+    token: CancellationToken,
+    started_at: Instant,
+}
+
+/// 某个 tag 下正在运行的任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub tag: String,
+    pub count: usize,
+    /// 最早启动的任务已运行的时长
+    pub oldest_age_ms: u64,
+}
+
+/// shutdown 的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    /// 在超时前自行结束的任务数
+    pub drained: usize,
+    /// 超时后被强制终止的任务数
+    pub aborted: usize,
+}
 
 /// 批量管理异步任务，在合适的时机终止
 pub struct TaskManager {
-    tasks: Arc<Mutex<HashMap<String, Vec<JoinHandle<()>>>>>,
+    tasks: Arc<Mutex<HashMap<String, Vec<TaskEntry>>>>,
+    token: CancellationToken,
 }
 
 static INSTANCE: LazyLock<TaskManager> = LazyLock::new(TaskManager::new);
@@ -17,6 +52,7 @@ impl TaskManager {
     fn new() -> Self {
         Self {
             tasks: Arc::new(Mutex::new(HashMap::new())),
+            token: CancellationToken::new(),
         }
     }
 
@@ -24,20 +60,131 @@ impl TaskManager {
         &INSTANCE
     }
 
+    /// 全局的取消信号，shutdown 开始时触发
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
     pub async fn add(&self, tag: &str, handle: JoinHandle<()>) {
+        self.insert(tag, handle, self.token.child_token()).await;
+    }
+
+    /// 启动一个可协作取消的任务，任务应在 token 被取消后尽快收尾
+    pub async fn spawn<F, Fut>(&self, tag: &str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let token = self.token.child_token();
+        let handle = tokio::spawn(task(token.clone()));
+        self.insert(tag, handle, token).await;
+    }
+
+    /// 启动一个逐个按顺序处理消息的任务，返回发送端；取消后不再开始处理新的消息
+    pub async fn spawn_queue<T, F, Fut>(&self, tag: &str, handler: F) -> mpsc::UnboundedSender<T>
+    where
+        T: Send + 'static,
+        F: Fn(T) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        self.spawn(tag, move |token| async move {
+            loop {
+                let item = tokio::select! {
+                    biased;
+                    _ = token.cancelled() => break,
+                    item = receiver.recv() => item,
+                };
+                let Some(item) = item else {
+                    break;
+                };
+                handler(item).await;
+            }
+        })
+        .await;
+        sender
+    }
+
+    async fn insert(&self, tag: &str, handle: JoinHandle<()>, token: CancellationToken) {
         let mut tasks = self.tasks.lock().await;
-        let handles = tasks.entry(tag.to_string()).or_insert_with(Vec::new);
-        handles.retain(|h| !h.is_finished());
-        handles.push(handle);
+        let entries = tasks.entry(tag.to_string()).or_default();
+        entries.retain(|entry| !entry.handle.is_finished());
+        entries.push(TaskEntry {
+            handle,
+            token,
+            started_at: Instant::now(),
+        });
     }
 
+    /// 立即终止某个 tag 下的所有任务
     pub async fn dispose(&self, tag: &str) {
         let mut tasks = self.tasks.lock().await;
-        if let Some(handles) = tasks.remove(tag) {
-            for handle in handles {
-                let _ = handle.abort();
+        if let Some(entries) = tasks.remove(tag) {
+            for entry in entries {
+                entry.token.cancel();
+                entry.handle.abort();
+            }
+        }
+    }
+
+    /// 取消某个 tag 下的所有任务，等待它们在 timeout 内收尾，超时后强制终止
+    pub async fn cancel(&self, tag: &str, timeout: Duration) -> ShutdownReport {
+        let entries = self.tasks.lock().await.remove(tag).unwrap_or_default();
+        TaskManager::drain(entries, timeout).await
+    }
+
+    /// 取消所有任务并等待它们在 timeout 内收尾，超时后强制终止
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        self.token.cancel();
+        let entries = self
+            .tasks
+            .lock()
+            .await
+            .drain()
+            .flat_map(|(_, entries)| entries)
+            .collect::<Vec<_>>();
+        TaskManager::drain(entries, timeout).await
+    }
+
+    async fn drain(entries: Vec<TaskEntry>, timeout: Duration) -> ShutdownReport {
+        let mut handles = Vec::with_capacity(entries.len());
+        for entry in entries {
+            entry.token.cancel();
+            handles.push(entry.handle);
+        }
+
+        let _ = tokio::time::timeout(timeout, join_all(handles.iter_mut())).await;
+
+        let mut report = ShutdownReport::default();
+        for handle in handles {
+            if handle.is_finished() {
+                report.drained += 1;
+            } else {
+                handle.abort();
+                report.aborted += 1;
             }
         }
+        report
+    }
+
+    /// 当前仍在运行的任务，按 tag 汇总
+    pub async fn snapshot(&self) -> Vec<TaskInfo> {
+        let mut tasks = self.tasks.lock().await;
+        let mut snapshot = Vec::new();
+        for (tag, entries) in tasks.iter_mut() {
+            entries.retain(|entry| !entry.handle.is_finished());
+            let Some(oldest) = entries.iter().map(|entry| entry.started_at).min() else {
+                continue;
+            };
+            snapshot.push(TaskInfo {
+                tag: tag.clone(),
+                count: entries.len(),
+                oldest_age_ms: oldest.elapsed().as_millis() as u64,
+            });
+        }
+        tasks.retain(|_, entries| !entries.is_empty());
+        snapshot.sort_by(|a, b| a.tag.cmp(&b.tag));
+        snapshot
     }
 
     pub async fn run_async<F>(future: F)
@@ -56,3 +203,386 @@ impl TaskManager {
         TaskManager::instance().dispose("async").await;
     }
 }
+
+/// 子任务的重启策略，失败后按指数退避重启
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// 连续运行超过这个时长后，退避时间重新从 initial_backoff 开始
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(60),
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChildState {
+    Running,
+    /// 已退出，等待重启
+    Backoff,
+}
+
+/// 子任务的健康状况
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChildHealth {
+    pub name: String,
+    pub state: ChildState,
+    pub restarts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// 本次运行的时长，只有 Running 时才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_ms: Option<u64>,
+}
+
+struct ChildRecord {
+    state: ChildState,
+    restarts: u32,
+    last_error: Option<String>,
+    started_at: Option<Instant>,
+    supervisor: AbortHandle,
+    current: Option<AbortHandle>,
+}
+
+/// 监管一组具名的长期任务：任务出错或退出后按退避策略自动重启
+///
+/// release 构建使用 `panic = "abort"`，panic 会直接结束进程，不会被重启
+pub struct Supervisor {
+    children: Arc<Mutex<HashMap<String, ChildRecord>>>,
+}
+
+static SUPERVISOR: LazyLock<Supervisor> = LazyLock::new(Supervisor::new);
+
+impl Supervisor {
+    fn new() -> Self {
+        Self {
+            children: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn instance() -> &'static Self {
+        &SUPERVISOR
+    }
+
+    /// 以默认策略监管任务，同名任务会先被停止
+    pub async fn spawn<F, Fut>(&self, name: &str, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        self.spawn_with(name, RestartPolicy::default(), factory)
+            .await;
+    }
+
+    pub async fn spawn_with<F, Fut>(&self, name: &str, policy: RestartPolicy, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        self.stop(name).await;
+
+        // 先持有锁再启动，保证监管循环第一次更新状态时记录已经存在
+        let mut children = self.children.lock().await;
+        let records = Arc::clone(&self.children);
+        let key = name.to_string();
+        let supervisor = tokio::spawn(async move {
+            let mut backoff = policy.initial_backoff;
+            loop {
+                let future = factory();
+                let child = tokio::spawn(async move { future.await.map_err(|e| e.to_string()) });
+                let started_at = Instant::now();
+                {
+                    let mut records = records.lock().await;
+                    let Some(record) = records.get_mut(&key) else {
+                        child.abort();
+                        return;
+                    };
+                    record.state = ChildState::Running;
+                    record.started_at = Some(started_at);
+                    record.current = Some(child.abort_handle());
+                }
+
+                let error = match child.await {
+                    Ok(Ok(())) => "exited".to_string(),
+                    Ok(Err(e)) => e,
+                    Err(e) => describe_join_error(e),
+                };
+
+                if started_at.elapsed() >= policy.reset_after {
+                    backoff = policy.initial_backoff;
+                }
+
+                {
+                    let mut records = records.lock().await;
+                    let Some(record) = records.get_mut(&key) else {
+                        return;
+                    };
+                    record.state = ChildState::Backoff;
+                    record.started_at = None;
+                    record.current = None;
+                    record.restarts += 1;
+                    record.last_error = Some(error.clone());
+                }
+                eprintln!(
+                    "⚠️  [Supervisor] {} {}, restarting in {}ms",
+                    key,
+                    error,
+                    backoff.as_millis()
+                );
+
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(policy.max_backoff);
+            }
+        });
+
+        children.insert(
+            name.to_string(),
+            ChildRecord {
+                state: ChildState::Backoff,
+                restarts: 0,
+                last_error: None,
+                started_at: None,
+                supervisor: supervisor.abort_handle(),
+                current: None,
+            },
+        );
+    }
+
+    /// 停止监管并终止任务
+    pub async fn stop(&self, name: &str) {
+        if let Some(record) = self.children.lock().await.remove(name) {
+            record.supervisor.abort();
+            if let Some(current) = record.current {
+                current.abort();
+            }
+        }
+    }
+
+    pub async fn stop_all(&self) {
+        let mut children = self.children.lock().await;
+        for (_, record) in children.drain() {
+            record.supervisor.abort();
+            if let Some(current) = record.current {
+                current.abort();
+            }
+        }
+    }
+
+    pub async fn health(&self) -> Vec<ChildHealth> {
+        let children = self.children.lock().await;
+        let mut health = children
+            .iter()
+            .map(|(name, record)| ChildHealth {
+                name: name.clone(),
+                state: record.state,
+                restarts: record.restarts,
+                last_error: record.last_error.clone(),
+                uptime_ms: record
+                    .started_at
+                    .map(|started_at| started_at.elapsed().as_millis() as u64),
+            })
+            .collect::<Vec<_>>();
+        health.sort_by(|a, b| a.name.cmp(&b.name));
+        health
+    }
+
+    /// 至少有一个任务，且所有任务都在运行
+    pub async fn is_healthy(&self) -> bool {
+        let children = self.children.lock().await;
+        !children.is_empty()
+            && children
+                .values()
+                .all(|record| record.state == ChildState::Running)
+    }
+}
+
+fn describe_join_error(error: JoinError) -> String {
+    if !error.is_panic() {
+        return "cancelled".to_string();
+    }
+    let payload = error.into_panic();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::BoxFuture;
+    use std::sync::Mutex as StdMutex;
+
+    type Starts = Arc<StdMutex<Vec<Instant>>>;
+
+    /// 第 i 次运行 `runs[i]` 后失败的任务，之后一直按最后一项运行，返回每次启动的时间
+    fn flaky(
+        runs: Vec<Duration>,
+    ) -> (
+        Starts,
+        impl Fn() -> BoxFuture<'static, Result<(), AppError>>,
+    ) {
+        let starts = Starts::default();
+        let recorded = Arc::clone(&starts);
+        let factory = move || {
+            let run = {
+                let mut starts = recorded.lock().unwrap();
+                starts.push(Instant::now());
+                runs.get(starts.len() - 1).copied().unwrap_or_default()
+            };
+            Box::pin(async move {
+                sleep(run).await;
+                Err::<(), AppError>("boom".into())
+            }) as BoxFuture<'static, _>
+        };
+        (starts, factory)
+    }
+
+    async fn wait_for_starts(starts: &StdMutex<Vec<Instant>>, count: usize) -> Vec<Duration> {
+        while starts.lock().unwrap().len() < count {
+            sleep(Duration::from_millis(10)).await;
+        }
+        let starts = starts.lock().unwrap();
+        starts[..count]
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_drains_tasks_that_honour_the_token() {
+        let manager = TaskManager::new();
+        for _ in 0..2 {
+            manager
+                .spawn("worker", |token| async move {
+                    token.cancelled().await;
+                    sleep(Duration::from_millis(100)).await;
+                })
+                .await;
+        }
+
+        let report = manager.shutdown(Duration::from_secs(1)).await;
+        assert_eq!((report.drained, report.aborted), (2, 0));
+        assert!(manager.snapshot().await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_aborts_tasks_that_ignore_the_token() {
+        let manager = TaskManager::new();
+        manager
+            .spawn("polite", |token| async move { token.cancelled().await })
+            .await;
+        manager
+            .spawn("stubborn", |_| sleep(Duration::from_secs(3600)))
+            .await;
+
+        let started = Instant::now();
+        let report = manager.shutdown(Duration::from_secs(1)).await;
+        assert_eq!((report.drained, report.aborted), (1, 1));
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queue_handles_items_one_at_a_time_in_order() {
+        let manager = TaskManager::new();
+        let handled = Arc::new(StdMutex::new(Vec::new()));
+        let busy = Arc::new(StdMutex::new(false));
+        let sender = manager
+            .spawn_queue("dialog", {
+                let handled = Arc::clone(&handled);
+                move |i: u64| {
+                    let (handled, busy) = (Arc::clone(&handled), Arc::clone(&busy));
+                    async move {
+                        assert!(!std::mem::replace(&mut *busy.lock().unwrap(), true));
+                        // 越早的任务耗时越长，并发执行时会最后完成
+                        sleep(Duration::from_millis(100 * (5 - i))).await;
+                        handled.lock().unwrap().push(i);
+                        *busy.lock().unwrap() = false;
+                    }
+                }
+            })
+            .await;
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+        sleep(Duration::from_secs(2)).await;
+        assert_eq!(*handled.lock().unwrap(), [0, 1, 2, 3, 4]);
+
+        manager.shutdown(Duration::from_secs(1)).await;
+        assert!(sender.send(5).is_err());
+    }
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(400),
+            reset_after: Duration::from_millis(500),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failing_task_restarts_with_growing_backoff() {
+        let supervisor = Supervisor::new();
+        let (starts, factory) = flaky(Vec::new());
+        supervisor.spawn_with("flaky", policy(), factory).await;
+
+        let delays = wait_for_starts(&starts, 5).await;
+        let ms: Vec<u128> = delays.iter().map(Duration::as_millis).collect();
+        assert_eq!(ms, [100, 200, 400, 400]);
+
+        let health = supervisor.health().await;
+        assert_eq!(health[0].name, "flaky");
+        assert!(health[0].restarts >= 4);
+        assert_eq!(health[0].last_error.as_deref(), Some("boom"));
+        assert!(!supervisor.is_healthy().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_resets_after_a_healthy_run() {
+        let supervisor = Supervisor::new();
+        let second = Duration::from_secs(1);
+        let (starts, factory) = flaky(vec![Duration::ZERO, Duration::ZERO, second]);
+        supervisor.spawn_with("flaky", policy(), factory).await;
+
+        let delays = wait_for_starts(&starts, 4).await;
+        let ms: Vec<u128> = delays.iter().map(Duration::as_millis).collect();
+        // 第三次运行超过了 reset_after，下次重启重新从 100ms 开始
+        assert_eq!(ms, [100, 200, 1_100]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stopping_ends_restarts_and_the_running_task() {
+        let supervisor = Supervisor::new();
+        let (starts, factory) = flaky(Vec::new());
+        supervisor.spawn_with("flaky", policy(), factory).await;
+        wait_for_starts(&starts, 2).await;
+
+        supervisor.stop("flaky").await;
+        let started = starts.lock().unwrap().len();
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(starts.lock().unwrap().len(), started);
+        assert!(supervisor.health().await.is_empty());
+
+        // 停止时正在运行的任务会被中止
+        let (running, factory) = flaky(vec![Duration::from_secs(3600)]);
+        supervisor.spawn_with("long", policy(), factory).await;
+        wait_for_starts(&running, 1).await;
+        assert!(supervisor.is_healthy().await);
+        supervisor.stop_all().await;
+        sleep(Duration::from_secs(7200)).await;
+        assert_eq!(running.lock().unwrap().len(), 1);
+        assert!(!supervisor.is_healthy().await);
+    }
+}
@@ -0,0 +1,40 @@
+use open_xiaoai::services::auth;
+
+#[test]
+fn hmac_matches_rfc_4231() {
+    // Test case 2
+    let mac = auth::hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+    assert_eq!(
+        hex::encode(mac),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    // Test case 6, key longer than one block
+    let mac = auth::hmac_sha256(
+        &[0xaa; 131],
+        b"Test Using Larger Than Block-Size Key - Hash Key First",
+    );
+    assert_eq!(
+        hex::encode(mac),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[test]
+fn verifies_signed_requests() {
+    let signed_at = 1_700_000_000;
+    let body: &[u8] = br#"{"ids":["1"]}"#;
+    let signature = auth::sign("secret", "post", "/commands/speaker/ack", signed_at, body);
+    let verify = |secret: &str, path: &str, body: &[u8], now: i64| {
+        auth::verify(secret, "POST", path, signed_at, body, &signature, now)
+    };
+
+    let soon = signed_at + 10;
+    assert!(verify("secret", "/commands/speaker/ack", body, soon));
+    assert!(!verify("other", "/commands/speaker/ack", body, signed_at));
+    assert!(!verify("secret", "/commands/other/ack", body, signed_at));
+    assert!(!verify("secret", "/commands/speaker/ack", b"{}", signed_at));
+    // Replayed long after it was signed
+    let late = signed_at + auth::MAX_CLOCK_SKEW + 1;
+    assert!(!verify("secret", "/commands/speaker/ack", body, late));
+}
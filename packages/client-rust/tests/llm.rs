@@ -0,0 +1,104 @@
+use open_xiaoai::services::config::OpenAIConfig;
+use open_xiaoai::services::llm::ProviderChain;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use warp::{Filter, Reply};
+
+/// A fake chat completions endpoint replying with `replies` in turn, repeating the last one.
+/// Returns its address and a counter of the requests it received.
+fn provider(
+    model: &'static str,
+    replies: Vec<(u16, Option<&'static str>)>,
+) -> (SocketAddr, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&hits);
+    let route = warp::path!("chat" / "completions").map(move || {
+        let hit = counter.fetch_add(1, Ordering::SeqCst);
+        let (status, retry_after) = replies[hit.min(replies.len() - 1)];
+        let body =
+            json!({ "choices": [{ "message": { "content": format!("hi from {}", model) } }] });
+        let status = warp::http::StatusCode::from_u16(status).unwrap();
+        let mut response =
+            warp::reply::with_status(warp::reply::json(&body), status).into_response();
+        if let Some(retry_after) = retry_after {
+            response
+                .headers_mut()
+                .insert("retry-after", retry_after.parse().unwrap());
+        }
+        response
+    });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    (addr, hits)
+}
+
+fn chain(primary: SocketAddr, backup: SocketAddr) -> ProviderChain {
+    let config: OpenAIConfig = serde_json::from_value(json!({
+        "baseURL": format!("http://{}", primary),
+        "apiKey": "primary-key",
+        "model": "primary",
+        "timeout": 5,
+        "fallbacks": [{
+            "name": "backup",
+            "baseURL": format!("http://{}", backup),
+            "apiKey": "backup-key",
+            "model": "backup"
+        }]
+    }))
+    .unwrap();
+    ProviderChain::new(&config)
+}
+
+async fn ask(chain: &ProviderChain) -> Result<(String, String), String> {
+    let body = json!({ "messages": [{ "role": "user", "content": "hello" }] });
+    let (response, provider) = chain.complete(body).await.map_err(|e| e.to_string())?;
+    let content = response["choices"][0]["message"]["content"]
+        .as_str()
+        .unwrap_or_default();
+    Ok((content.to_string(), provider))
+}
+
+#[tokio::test]
+async fn retries_a_rate_limited_provider_after_retry_after() {
+    let (primary, primary_hits) = provider("primary", vec![(429, Some("0")), (200, None)]);
+    let (backup, backup_hits) = provider("backup", vec![(200, None)]);
+    let chain = chain(primary, backup);
+
+    let (content, served_by) = ask(&chain).await.unwrap();
+    assert_eq!(content, "hi from primary");
+    assert_eq!(served_by, "127.0.0.1");
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 2);
+    assert_eq!(backup_hits.load(Ordering::SeqCst), 0);
+    assert_eq!(chain.status()["lastServed"], "127.0.0.1");
+}
+
+#[tokio::test]
+async fn fails_over_once_retries_are_used_up() {
+    let (primary, primary_hits) = provider("primary", vec![(503, Some("0"))]);
+    let (backup, _) = provider("backup", vec![(200, None)]);
+    let chain = chain(primary, backup);
+
+    let (content, served_by) = ask(&chain).await.unwrap();
+    assert_eq!(content, "hi from backup");
+    assert_eq!(served_by, "backup");
+    // One attempt plus the default single retry
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 2);
+    let status: Value = chain.status();
+    assert_eq!(status["lastServed"], "backup");
+    assert_eq!(status["providers"][0]["failures"], 1);
+    assert_eq!(status["providers"][1]["served"], 1);
+}
+
+#[tokio::test]
+async fn does_not_wait_out_long_retry_after_or_retry_client_errors() {
+    let (primary, primary_hits) = provider("primary", vec![(429, Some("120"))]);
+    let (backup, backup_hits) = provider("backup", vec![(400, None)]);
+    let chain = chain(primary, backup);
+
+    let error = ask(&chain).await.unwrap_err();
+    assert!(error.starts_with("All LLM providers failed"), "{}", error);
+    assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(backup_hits.load(Ordering::SeqCst), 1);
+}
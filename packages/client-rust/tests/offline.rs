@@ -0,0 +1,103 @@
+use open_xiaoai::services::connect::data::Event;
+use open_xiaoai::services::outbox::{HandledCommands, Outbox};
+use open_xiaoai::services::retry::{self, Backoff, CircuitBreaker};
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn outbox_survives_restarts_and_drops_the_oldest() {
+    let path = std::env::temp_dir()
+        .join(format!("open-xiaoai-outbox-{}", uuid::Uuid::new_v4()))
+        .join("outbox.jsonl");
+    let events: Vec<Event> = (0..3)
+        .map(|i| Event::new("instruction", json!({ "text": i })))
+        .collect();
+
+    let outbox = Outbox::open(&path, 2);
+    assert!(outbox.push(events[0].clone()).is_none());
+    assert!(outbox.push(events[1].clone()).is_none());
+    let dropped = outbox.push(events[2].clone()).unwrap();
+    assert_eq!(dropped.id, events[0].id);
+
+    let reopened = Outbox::open(&path, 2);
+    assert_eq!(reopened.len(), 2);
+    assert_eq!(reopened.front().unwrap().id, events[1].id);
+
+    reopened.remove(&events[1].id);
+    reopened.remove(&events[2].id);
+    assert!(reopened.is_empty());
+    assert!(!path.exists());
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn handled_commands_survive_a_restart() {
+    let dir = std::env::temp_dir().join(format!("open-xiaoai-handled-{}", uuid::Uuid::new_v4()));
+    let path = dir.join("handled-commands");
+
+    let mut handled = HandledCommands::open(&path);
+    assert!(handled.insert("first"));
+    assert!(!handled.insert("first"));
+    for i in 0..HandledCommands::CAPACITY {
+        handled.insert(&i.to_string());
+    }
+
+    let reopened = HandledCommands::open(&path);
+    assert!(!reopened.contains("first"));
+    assert!(reopened.contains("0"));
+    assert!(reopened.contains(&(HandledCommands::CAPACITY - 1).to_string()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn backoff_grows_with_jitter_up_to_the_cap() {
+    let mut backoff = Backoff::new(Duration::from_secs(4), Duration::from_secs(10));
+    let delays: Vec<Duration> = (0..4).map(|_| backoff.next_delay()).collect();
+    let bounds = [(2, 4), (4, 8), (5, 10), (5, 10)];
+    for (delay, (low, high)) in delays.iter().zip(bounds) {
+        assert!(*delay >= Duration::from_secs(low) && *delay <= Duration::from_secs(high));
+    }
+
+    backoff.reset();
+    assert!(backoff.next_delay() <= Duration::from_secs(4));
+}
+
+#[test]
+fn breaker_opens_after_repeated_failures() {
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+    assert!(!breaker.record_failure());
+    assert!(breaker.allow());
+    assert!(breaker.record_failure());
+    assert!(!breaker.allow());
+    assert!(breaker.is_open());
+
+    breaker.record_success();
+    assert!(breaker.allow());
+    assert!(!breaker.is_open());
+
+    // Once the cooldown is over a probe request is let through
+    let breaker = CircuitBreaker::new(1, Duration::ZERO);
+    assert!(breaker.record_failure());
+    assert!(breaker.allow());
+    assert!(breaker.is_open());
+}
+
+#[test]
+fn retry_after_accepts_seconds_and_dates() {
+    let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+        .unwrap()
+        .to_utc();
+    assert_eq!(
+        retry::retry_after("120", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        retry::retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(
+        retry::retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(retry::retry_after("soon", now), None);
+}
@@ -0,0 +1,79 @@
+use open_xiaoai::services::speech::{self, Speech, SpeechConfig};
+
+#[test]
+fn reads_integers_the_chinese_way() {
+    let cases = [
+        (0, "零"),
+        (7, "七"),
+        (10, "十"),
+        (15, "十五"),
+        (105, "一百零五"),
+        (1010, "一千零一十"),
+        (10010, "一万零一十"),
+        (120_000, "十二万"),
+        (100_010_000, "一亿零一万"),
+    ];
+    for (n, spoken) in cases {
+        assert_eq!(speech::integer(n), spoken, "{}", n);
+    }
+}
+
+#[test]
+fn strips_markdown_into_sentences() {
+    let reply = "## 推荐 🎉\n\n1. **苹果**：富含维生素\n2. `香蕉`，详见 [百科](https://example.com)\n\n```python\nprint(1)\n```\n| 名称 | 价格 |\n|---|---|\n| 橙子 | 便宜 |";
+    assert_eq!(
+        speech::strip_markdown(reply),
+        "推荐，苹果：富含维生素，香蕉，详见 百科，名称，价格，橙子，便宜"
+    );
+}
+
+#[test]
+fn speaks_numbers_units_and_dates() {
+    let cases = [
+        (
+            "会议在2024-03-05 14:05开始",
+            "会议在二零二四年三月五日 十四点零五分开始",
+        ),
+        ("气温-3℃到12°C", "气温负三摄氏度到十二摄氏度"),
+        (
+            "电量还剩85%，续航12.5km",
+            "电量还剩百分之八十五，续航十二点五公里",
+        ),
+        ("一共$1,299", "一共一千二百九十九美元"),
+        ("建议睡7~8h", "建议睡七到八小时"),
+        ("每天3-5个", "每天三到五个"),
+        ("区号010-1234", "区号零一零-一千二百三十四"),
+        ("5-3=2", "五-三=二"),
+        ("24:00", "二十四:零零"),
+        (
+            "报警电话110，客服13800138000",
+            "报警电话一百一十，客服一三八零零一三八零零零",
+        ),
+        ("iPhone 15 Pro", "iPhone 十五 Pro"),
+    ];
+    for (text, spoken) in cases {
+        assert_eq!(speech::speak_numbers(text), spoken);
+    }
+}
+
+#[test]
+fn long_replies_continue_on_request() {
+    let speech = Speech::new(SpeechConfig {
+        max_chars: 10,
+        ..SpeechConfig::default()
+    });
+
+    let first = speech.speak("第一句话很短。第二句话也不长。第三句。");
+    assert_eq!(first, "第一句话很短。要我继续说下去吗？");
+    assert_eq!(
+        speech.resume("继续。").as_deref(),
+        Some("第二句话也不长。要我继续说下去吗？")
+    );
+    assert_eq!(speech.resume("好的").as_deref(), Some("第三句。"));
+    assert_eq!(speech.resume("继续"), None);
+
+    // Asking something else drops the rest
+    speech.speak("第一句话很短。第二句话也不长。");
+    assert_eq!(speech.resume("今天天气怎么样"), None);
+    assert_eq!(speech.resume("继续"), None);
+}
@@ -0,0 +1,148 @@
+use ed25519_dalek::{Signer, SigningKey};
+use open_xiaoai::services::update::{self, Manifest, UpdateConfig, Updater};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use warp::Filter;
+
+const OLD_BINARY: &[u8] = b"old client binary";
+const NEW_BINARY: &[u8] = b"new client binary";
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+fn public_key_hex() -> String {
+    hex::encode(signing_key().verifying_key().to_bytes())
+}
+
+fn manifest_for(version: &str, bytes: &[u8], key: &SigningKey) -> Manifest {
+    let digest = Sha256::digest(bytes);
+    Manifest {
+        version: version.to_string(),
+        url: "client.bin".to_string(),
+        sha256: hex::encode(digest),
+        signature: hex::encode(
+            key.sign(&update::signed_message(version, &digest))
+                .to_bytes(),
+        ),
+    }
+}
+
+/// Serves /manifest.json and /client.bin on an ephemeral local port
+fn serve(manifest: Manifest, binary: &'static [u8]) -> SocketAddr {
+    let manifest = warp::path!("manifest.json").map(move || warp::reply::json(&manifest));
+    let binary = warp::path!("client.bin").map(move || binary.to_vec());
+    let (addr, server) = warp::serve(manifest.or(binary)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+fn install_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("open-xiaoai-update-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let binary = dir.join("client");
+    std::fs::write(&binary, OLD_BINARY).unwrap();
+    binary
+}
+
+fn updater(addr: SocketAddr, binary: &PathBuf) -> Updater {
+    Updater::new(&format!("http://{}/manifest.json", addr), &public_key_hex())
+        .unwrap()
+        .with_binary(binary)
+}
+
+#[tokio::test]
+async fn installs_newer_release_and_keeps_previous() {
+    let addr = serve(
+        manifest_for("99.0.0", NEW_BINARY, &signing_key()),
+        NEW_BINARY,
+    );
+    let binary = install_dir();
+    let updater = updater(addr, &binary);
+
+    let installed = updater.update().await.unwrap();
+
+    assert_eq!(installed.as_deref(), Some("99.0.0"));
+    assert_eq!(std::fs::read(&binary).unwrap(), NEW_BINARY);
+    assert_eq!(
+        std::fs::read(updater.previous_binary()).unwrap(),
+        OLD_BINARY
+    );
+
+    updater.rollback().unwrap();
+    assert_eq!(std::fs::read(&binary).unwrap(), OLD_BINARY);
+}
+
+#[tokio::test]
+async fn skips_release_that_is_not_newer() {
+    let addr = serve(
+        manifest_for("0.0.1", NEW_BINARY, &signing_key()),
+        NEW_BINARY,
+    );
+    let binary = install_dir();
+
+    let installed = updater(addr, &binary).update().await.unwrap();
+
+    assert_eq!(installed, None);
+    assert_eq!(std::fs::read(&binary).unwrap(), OLD_BINARY);
+}
+
+#[tokio::test]
+async fn rejects_binary_with_wrong_digest() {
+    let manifest = manifest_for("99.0.0", NEW_BINARY, &signing_key());
+    let addr = serve(manifest, b"tampered client binary");
+    let binary = install_dir();
+
+    let error = updater(addr, &binary).update().await.unwrap_err();
+
+    assert!(error.to_string().contains("sha256"), "{}", error);
+    assert_eq!(std::fs::read(&binary).unwrap(), OLD_BINARY);
+}
+
+#[tokio::test]
+async fn rejects_release_signed_by_another_key() {
+    let other_key = SigningKey::from_bytes(&[9; 32]);
+    let addr = serve(manifest_for("99.0.0", NEW_BINARY, &other_key), NEW_BINARY);
+    let binary = install_dir();
+
+    let error = updater(addr, &binary).update().await.unwrap_err();
+
+    assert!(error.to_string().contains("signature"), "{}", error);
+    assert_eq!(std::fs::read(&binary).unwrap(), OLD_BINARY);
+}
+
+#[tokio::test]
+async fn rejects_old_release_relabelled_as_newer() {
+    let mut manifest = manifest_for("0.0.1", OLD_BINARY, &signing_key());
+    manifest.version = "99.0.0".to_string();
+    let addr = serve(manifest, OLD_BINARY);
+    let binary = install_dir();
+
+    let error = updater(addr, &binary).update().await.unwrap_err();
+
+    assert!(error.to_string().contains("signature"), "{}", error);
+}
+
+#[test]
+fn refuses_plain_http_to_remote_hosts() {
+    let result = Updater::new("http://example.com/manifest.json", &public_key_hex());
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn trusts_the_proxy_for_the_default_manifest() {
+    let config = UpdateConfig {
+        url: None,
+        public_key: public_key_hex(),
+        check_interval: 60,
+    };
+    assert!(Updater::from_config(&config, Some("http://192.168.1.10:4399")).is_ok());
+
+    let config = UpdateConfig {
+        url: Some("http://192.168.1.10:4399/update/manifest.json".to_string()),
+        ..config
+    };
+    assert!(Updater::from_config(&config, Some("http://192.168.1.10:4399")).is_err());
+}